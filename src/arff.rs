@@ -1,113 +1,1174 @@
-use crate::record::Record;
+use crate::date;
+use crate::record::{map_features, Features, Record, Value};
+use crate::discretize::{Binning, Discretizer};
+use crate::scale::{Scaler, Scaling};
+use itertools::Itertools;
 use lazy_static::lazy_static;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 use regex::{Regex, RegexBuilder};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Lines, Write};
+use std::str::FromStr;
+use std::sync::Arc;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Class {
     Nominal(Vec<String>),
-    // Max
-    Continuous(usize),
+    Continuous,
+    /// Free text, every distinct value seen so far in order of appearance.
+    /// Trees don't learn from it unless it is converted to nominal.
+    String(Vec<String>),
+    /// A timestamp in the declared `SimpleDateFormat` style format, stored
+    /// and learned from as seconds since the Unix epoch in UTC. Only the
+    /// numeric fields `y`, `M`, `d`, `H`, `m`, `s` and `S` and quoted or
+    /// punctuation literals are supported; month names, `a`, `h`, time zones
+    /// and other letters make every value fail to parse.
+    Date(String),
 }
 
 #[derive(Debug)]
 pub struct Arff {
+    pub relation: String,
     pub records: Vec<Record>,
     pub labels: Vec<String>,
     pub classes: Vec<Class>,
+    /// Attribute index of the class
+    pub target: usize,
+    /// Scaling fitted to the numeric attributes, applied again to records
+    /// conformed to this dataset
+    pub scaler: Option<Scaler>,
+    /// Cut points that turned continuous attributes nominal, applied again
+    /// to records conformed to this dataset
+    pub discretizer: Option<Discretizer>,
 }
 
-#[derive(Debug)]
-pub enum ArffError {}
+/// Which attribute is the class, like Weka's `-c`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum Target {
+    First,
+    #[default]
+    Last,
+    /// 0-based attribute index
+    Index(usize),
+    Name(String),
+}
 
-impl Arff {
-    pub fn parse(contents: &str) -> Result<Self, ArffError> {
+impl FromStr for Target {
+    type Err = String;
+
+    /// `first`, `last`, a 1-based attribute index or an attribute name
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "first" => Ok(Target::First),
+            "last" => Ok(Target::Last),
+            _ => match name.parse::<usize>() {
+                Ok(0) => Err(String::from("attribute indices start at 1")),
+                Ok(index) => Ok(Target::Index(index - 1)),
+                Err(_) => Ok(Target::Name(name.to_owned())),
+            },
+        }
+    }
+}
+
+impl Target {
+    fn resolve(&self, labels: &[String]) -> Option<usize> {
+        match self {
+            Target::First => Some(0).filter(|_| !labels.is_empty()),
+            Target::Last => labels.len().checked_sub(1),
+            Target::Index(index) => Some(*index).filter(|index| *index < labels.len()),
+            Target::Name(name) => labels.iter().position(|label| label == name),
+        }
+    }
+}
+
+/// Every variant carries the 1-based line number, and all but `Io` the
+/// 0-based column index and the name of the attribute the problem was found
+/// in.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArffError {
+    UnknownNominal {
+        line: usize,
+        column: usize,
+        attribute: String,
+        value: String,
+    },
+    BadNumber {
+        line: usize,
+        column: usize,
+        attribute: String,
+        value: String,
+    },
+    /// `column` is the first column that is missing or unexpected, and
+    /// `attribute` is empty when the row has more values than attributes.
+    WrongColumnCount {
+        line: usize,
+        column: usize,
+        attribute: String,
+        expected: usize,
+        found: usize,
+    },
+    BadDate {
+        line: usize,
+        column: usize,
+        attribute: String,
+        value: String,
+        format: String,
+    },
+    /// A sparse `index value` pair whose index isn't a column, or that has
+    /// no value. `column` is one past the last column.
+    BadSparseEntry {
+        line: usize,
+        column: usize,
+        attribute: String,
+        value: String,
+    },
+    /// A quote that isn't closed before the end of the line
+    UnterminatedQuote {
+        line: usize,
+        column: usize,
+        attribute: String,
+    },
+    /// `attribute` is the requested class attribute
+    UnknownTarget {
+        line: usize,
+        column: usize,
+        attribute: String,
+    },
+    /// The underlying reader failed
+    Io { line: usize, message: String },
+    DataBeforeDataSection {
+        line: usize,
+        column: usize,
+        attribute: String,
+    },
+    DuplicateAttribute {
+        line: usize,
+        column: usize,
+        attribute: String,
+    },
+    /// An `@attribute` declaration that starts with a brace instead of a
+    /// name. `column` is the index the attribute would have had.
+    MissingAttributeName { line: usize, column: usize },
+    /// A `string` or `date` declaration followed by more than a date format,
+    /// such as a format with spaces left unquoted. `value` is the type as
+    /// declared.
+    BadAttributeType {
+        line: usize,
+        column: usize,
+        attribute: String,
+        value: String,
+    },
+}
+
+impl fmt::Display for ArffError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArffError::UnknownNominal {
+                line,
+                column,
+                attribute,
+                value,
+            } => write!(
+                f,
+                "line {}, column {} ({}): unknown nominal value {:?}",
+                line, column, attribute, value
+            ),
+            ArffError::BadNumber {
+                line,
+                column,
+                attribute,
+                value,
+            } => write!(
+                f,
+                "line {}, column {} ({}): {:?} is not a number",
+                line, column, attribute, value
+            ),
+            ArffError::WrongColumnCount {
+                line,
+                column,
+                attribute,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}, column {} ({}): expected {} values, found {}",
+                line, column, attribute, expected, found
+            ),
+            ArffError::BadDate {
+                line,
+                column,
+                attribute,
+                value,
+                format,
+            } => write!(
+                f,
+                "line {}, column {} ({}): {:?} is not a date like {:?}",
+                line, column, attribute, value, format
+            ),
+            ArffError::BadSparseEntry { line, column, value, .. } => write!(
+                f,
+                "line {}, column {}: bad sparse entry {:?}",
+                line, column, value
+            ),
+            ArffError::UnterminatedQuote {
+                line,
+                column,
+                attribute,
+            } => write!(
+                f,
+                "line {}, column {} ({}): unterminated quote",
+                line, column, attribute
+            ),
+            ArffError::UnknownTarget {
+                line,
+                column,
+                attribute,
+            } => write!(
+                f,
+                "line {}, column {}: no attribute {} to use as the class",
+                line, column, attribute
+            ),
+            ArffError::Io { line, message } => write!(f, "line {}: {}", line, message),
+            ArffError::DataBeforeDataSection {
+                line,
+                column,
+                attribute,
+            } => write!(
+                f,
+                "line {}, column {} ({}): data found before @data",
+                line, column, attribute
+            ),
+            ArffError::DuplicateAttribute {
+                line,
+                column,
+                attribute,
+            } => write!(
+                f,
+                "line {}, column {}: attribute {} declared twice",
+                line, column, attribute
+            ),
+            ArffError::MissingAttributeName { line, column } => {
+                write!(f, "line {}, column {}: attribute without a name", line, column)
+            }
+            ArffError::BadAttributeType {
+                line,
+                column,
+                attribute,
+                value,
+            } => write!(
+                f,
+                "line {}, column {} ({}): bad attribute type {:?}",
+                line, column, attribute, value
+            ),
+        }
+    }
+}
+
+impl Error for ArffError {}
+
+impl Class {
+    /// Value of an attribute a sparse row leaves out.
+    fn default_value(&self) -> Value {
+        match self {
+            Class::Nominal(_) => Value::Nominal(0),
+            Class::Continuous | Class::Date(_) => Value::Continuous(0.),
+            Class::String(_) => Value::Missing,
+        }
+    }
+}
+
+/// Parses the unquoted value of the attribute in `column`. Only a bare `?` is
+/// missing.
+fn parse_value(
+    classes: &mut [Class],
+    labels: &[String],
+    line: usize,
+    column: usize,
+    (value, quoted): (&str, bool),
+) -> Result<Value, ArffError> {
+    let parsed = match &mut classes[column] {
+        _ if value == "?" && !quoted => Some(Value::Missing),
+        Class::Nominal(classes) => classes
+            .iter()
+            .position(|c| c == value)
+            .map(Value::Nominal),
+        // NaN and infinities would break sorting by value
+        Class::Continuous => value
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite())
+            .map(Value::Continuous),
+        Class::String(values) => Some(Value::Text(match values.iter().position(|v| v == value) {
+            Some(index) => index,
+            None => {
+                values.push(value.to_owned());
+                values.len() - 1
+            }
+        })),
+        Class::Date(format) => date::parse(value, format).map(Value::Continuous),
+    };
+    parsed.ok_or_else(|| {
+        let attribute = labels[column].clone();
+        let value = value.to_owned();
+        match classes[column] {
+            Class::Nominal(_) => ArffError::UnknownNominal {
+                line,
+                column,
+                attribute,
+                value,
+            },
+            Class::Continuous => ArffError::BadNumber {
+                line,
+                column,
+                attribute,
+                value,
+            },
+            Class::Date(ref format) => ArffError::BadDate {
+                line,
+                column,
+                attribute,
+                value,
+                format: format.clone(),
+            },
+            Class::String(_) => unreachable!("any text is a string"),
+        }
+    })
+}
+
+/// A piece of an ARFF line: a brace, or a value with its quotes and
+/// backslash escapes removed. Commas and whitespace only separate tokens.
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Word { text: String, quoted: bool },
+}
+
+fn unescape(escaped: char) -> char {
+    match escaped {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        other => other,
+    }
+}
+
+/// Splits `line` into tokens, or gives the index of the value whose quote is
+/// never closed.
+fn tokenize(line: &str) -> Result<Vec<Token>, usize> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    let unterminated = |tokens: &[Token]| tokens.iter().filter(|token| matches!(token, Token::Word { .. })).count();
+    while let Some(c) = chars.next() {
+        match c {
+            ',' => {}
+            _ if c.is_whitespace() => {}
+            '{' => tokens.push(Token::Open),
+            '}' => tokens.push(Token::Close),
+            '\'' | '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some(next) if next == c => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped) => text.push(unescape(escaped)),
+                            None => return Err(unterminated(&tokens)),
+                        },
+                        Some(next) => text.push(next),
+                        None => return Err(unterminated(&tokens)),
+                    }
+                }
+                tokens.push(Token::Word { text, quoted: true });
+            }
+            _ => {
+                let mut text = String::new();
+                let mut next = Some(c);
+                while let Some(c) = next {
+                    if c == '\\' {
+                        text.extend(chars.next().map(unescape));
+                    } else {
+                        text.push(c);
+                    }
+                    next = chars.next_if(|c| !(c.is_whitespace() || ",{}'\"".contains(*c)));
+                }
+                tokens.push(Token::Word { text, quoted: false });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Reads a data row: dense values or a sparse `{index value, ...}` block,
+/// optionally followed by an instance weight such as `{2.5}`.
+fn parse_record(
+    tokens: &[Token],
+    classes: &mut [Class],
+    labels: &[String],
+    target: usize,
+    line: usize,
+    defaults: &mut Option<Arc<Vec<Value>>>,
+) -> Result<Record, ArffError> {
+    let (row, weight) = match tokens {
+        [row @ .., Token::Open, Token::Word { text, .. }, Token::Close] if !row.is_empty() => {
+            match text.parse::<f64>() {
+                Ok(weight) if weight >= 0. && weight.is_finite() => (row, weight),
+                _ => {
+                    return Err(ArffError::BadNumber {
+                        line,
+                        column: classes.len(),
+                        attribute: String::from("weight"),
+                        value: text.clone(),
+                    })
+                }
+            }
+        }
+        _ => (tokens, 1.),
+    };
+    let words: Vec<(&str, bool)> = row
+        .iter()
+        .filter_map(|token| match token {
+            Token::Word { text, quoted } => Some((text.as_str(), *quoted)),
+            _ => None,
+        })
+        .collect();
+    if let [Token::Open, .., Token::Close] = row {
+        // Sparse row, every attribute left out takes its default
+        let defaults = defaults
+            .get_or_insert_with(|| Arc::new(classes.iter().map(Class::default_value).collect()))
+            .clone();
+        let mut values: Vec<(usize, Value)> = Vec::new();
+        for entry in words.chunks(2) {
+            let (index, value) = match entry {
+                [(index, false), value] => match index.parse::<usize>() {
+                    Ok(index) if index < classes.len() => (index, *value),
+                    _ => return Err(bad_sparse_entry(line, classes, entry)),
+                },
+                _ => return Err(bad_sparse_entry(line, classes, entry)),
+            };
+            values.push((index, parse_value(classes, labels, line, index, value)?));
+        }
+        values.sort_by_key(|(index, _)| *index);
+        let class = match values.binary_search_by_key(&target, |(index, _)| *index) {
+            Ok(position) => values[position].1,
+            Err(_) => defaults[target],
+        };
+        return Ok(Record {
+            features: Features::Sparse { values, defaults },
+            class,
+            weight,
+        });
+    }
+    if words.len() != classes.len() || words.len() != row.len() {
+        let column = words.len().min(classes.len());
+        return Err(ArffError::WrongColumnCount {
+            line,
+            column,
+            attribute: labels.get(column).cloned().unwrap_or_default(),
+            expected: classes.len(),
+            found: words.len(),
+        });
+    }
+    let values = words
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| parse_value(classes, labels, line, index, value))
+        .collect::<Result<Vec<Value>, ArffError>>()?;
+    Ok(Record {
+        class: values[target],
+        features: Features::Dense(values),
+        weight,
+    })
+}
+
+fn bad_sparse_entry(line: usize, classes: &[Class], entry: &[(&str, bool)]) -> ArffError {
+    ArffError::BadSparseEntry {
+        line,
+        column: classes.len(),
+        attribute: String::new(),
+        value: entry.iter().map(|(text, _)| *text).join(" "),
+    }
+}
+
+/// Predicted class index and the probability of every class.
+pub type Prediction = (usize, Vec<f64>);
+
+/// `text` as an ARFF value, quoted when it would otherwise be read back
+/// differently.
+fn quote(text: &str) -> String {
+    let plain = !text.is_empty()
+        && text != "?"
+        && !text.chars().any(|c| c.is_whitespace() || ",{}'\"\\%".contains(c));
+    if plain {
+        return text.to_owned();
+    }
+    let mut quoted = String::from("'");
+    for c in text.chars() {
+        match c {
+            '\'' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+/// Writes the relation and attribute declarations up to `@data`. A `scored`
+/// header adds a `predicted` attribute and a probability per class value of
+/// the `target` attribute.
+pub fn write_header<W: Write>(
+    writer: &mut W,
+    relation: &str,
+    labels: &[String],
+    classes: &[Class],
+    target: usize,
+    scored: bool,
+) -> io::Result<()> {
+    writeln!(writer, "@relation {}", quote(relation))?;
+    writeln!(writer)?;
+    for (label, class) in labels.iter().zip(classes) {
+        let kind = match class {
+            Class::Nominal(values) => format!("{{{}}}", values.iter().map(|value| quote(value)).join(", ")),
+            Class::Continuous => String::from("numeric"),
+            Class::String(_) => String::from("string"),
+            Class::Date(format) => format!("date {}", quote(format)),
+        };
+        writeln!(writer, "@attribute {} {}", quote(label), kind)?;
+    }
+    if let (true, Class::Nominal(values)) = (scored, &classes[target]) {
+        writeln!(writer, "@attribute predicted {{{}}}", values.iter().map(|value| quote(value)).join(", "))?;
+        for value in values {
+            writeln!(writer, "@attribute {} numeric", quote(&format!("probability {}", value)))?;
+        }
+    }
+    writeln!(writer)?;
+    writeln!(writer, "@data")
+}
+
+fn format_value(value: Value, class: &Class) -> String {
+    match (value, class) {
+        (Value::Missing, _) => String::from("?"),
+        (Value::Nominal(index), Class::Nominal(values)) => quote(&values[index]),
+        (Value::Text(index), Class::String(values)) => quote(&values[index]),
+        (Value::Continuous(seconds), Class::Date(format)) => quote(&date::format(seconds, format)),
+        (Value::Continuous(value), _) => format!("{}", value),
+        _ => unreachable!("values match their attribute"),
+    }
+}
+
+/// Writes one data line, sparse records in sparse form, followed by the
+/// `prediction` when the header was scored.
+pub fn write_record<W: Write>(
+    writer: &mut W,
+    record: &Record,
+    classes: &[Class],
+    target: usize,
+    prediction: Option<&Prediction>,
+) -> io::Result<()> {
+    let value = |index: usize| if index == target { record.class } else { record.features[index] };
+    let mut fields: Vec<String> = match &record.features {
+        Features::Dense(_) => (0..classes.len()).map(|index| format_value(value(index), &classes[index])).collect(),
+        // Whatever the record's own defaults, a reader takes the values
+        // left out as the attribute's default
+        Features::Sparse { .. } => (0..classes.len())
+            .filter(|index| value(*index) != classes[*index].default_value())
+            .map(|index| format!("{} {}", index, format_value(value(index), &classes[index])))
+            .collect(),
+    };
+    if let Some((predicted, probabilities)) = prediction {
+        let first = classes.len();
+        let scores = std::iter::once(format_value(Value::Nominal(*predicted), &classes[target]))
+            .chain(probabilities.iter().map(|probability| format!("{}", probability)));
+        match record.features {
+            Features::Dense(_) => fields.extend(scores),
+            Features::Sparse { .. } => {
+                fields.extend(scores.enumerate().map(|(offset, score)| format!("{} {}", first + offset, score)))
+            }
+        }
+    }
+    let row = fields.join(",");
+    let row = match record.features {
+        Features::Dense(_) => row,
+        Features::Sparse { .. } => format!("{{{}}}", row),
+    };
+    if record.weight == 1. {
+        writeln!(writer, "{}", row)
+    } else {
+        writeln!(writer, "{}, {{{}}}", row, record.weight)
+    }
+}
+
+pub(crate) fn resolve_target(target: &Target, labels: &[String], line: usize) -> Result<usize, ArffError> {
+    target.resolve(labels).ok_or_else(|| ArffError::UnknownTarget {
+        line,
+        column: 0,
+        attribute: match target {
+            Target::First => String::from("first"),
+            Target::Last => String::from("last"),
+            Target::Index(index) => format!("{}", index + 1),
+            Target::Name(name) => name.clone(),
+        },
+    })
+}
+
+/// Reads the header of an ARFF file up front, then yields its records one at
+/// a time so the data never has to fit in memory.
+pub struct ArffReader<R: BufRead> {
+    lines: Lines<R>,
+    line_number: usize,
+    pub relation: String,
+    pub labels: Vec<String>,
+    /// Attribute types. String attributes keep collecting values as records
+    /// are read.
+    pub classes: Vec<Class>,
+    /// Attribute index of the class
+    pub target: usize,
+    /// Shared by every sparse record, built at the first one
+    defaults: Option<Arc<Vec<Value>>>,
+}
+
+impl<R: BufRead> ArffReader<R> {
+    /// Reads the header up to and including `@data`, with the `target`
+    /// attribute as the class.
+    pub fn new(reader: R, target: &Target) -> Result<Self, ArffError> {
         lazy_static! {
-            static ref ATTRIBUTE: Regex = RegexBuilder::new(r"^@attribute\s+(\S+).*")
+            static ref ATTRIBUTE: Regex = RegexBuilder::new(r"^@attribute\s+(.*)")
                 .case_insensitive(true)
                 .build()
                 .unwrap();
-            static ref DATA: Regex = RegexBuilder::new("^@data")
+            static ref RELATION: Regex = RegexBuilder::new(r"^@relation\s+(.*)")
                 .case_insensitive(true)
                 .build()
                 .unwrap();
-            static ref NOMINAL: Regex = RegexBuilder::new(r"\{(.*)\}")
+            static ref DATA: Regex = RegexBuilder::new("^@data")
                 .case_insensitive(true)
                 .build()
                 .unwrap();
-            static ref CLASS: Regex = RegexBuilder::new(r"([^,]+),?")
-                .case_insensitive(true)
+            static ref HEADER: Regex = RegexBuilder::new("^@")
                 .build()
                 .unwrap();
         }
-        let mut records: Vec<Record> = Vec::new();
-        let mut labels = Vec::new();
-        let mut classes: Vec<Class> = Vec::new();
-        let mut data_section = false;
-        for line in contents.lines() {
-            if line.chars().next() != Some('%') {
-                if data_section {
-                    let values: Vec<usize> = line
-                        .split(',')
-                        .enumerate()
-                        .map(|(index, value)| match &mut classes[index] {
-                            Class::Nominal(classes) => {
-                                classes.iter().position(|ref c| *c == value).unwrap()
-                            }
-                            Class::Continuous(ref mut max) => {
-                                let continuous_value = value.parse::<f32>().unwrap().floor() as usize;
-                                if continuous_value > *max {
-                                    *max = continuous_value;
-                                }
-                                continuous_value
-                            },
+        let mut reader = Self {
+            lines: reader.lines(),
+            line_number: 0,
+            relation: String::new(),
+            labels: Vec::new(),
+            classes: Vec::new(),
+            target: 0,
+            defaults: None,
+        };
+        while let Some(line) = reader.next_line() {
+            let line = line?;
+            let line_number = reader.line_number;
+            let (labels, classes) = (&mut reader.labels, &mut reader.classes);
+            if let Some(declaration) = ATTRIBUTE.captures(line.trim()).and_then(|cap| cap.get(1)) {
+                let unterminated = |_| ArffError::UnterminatedQuote {
+                    line: line_number,
+                    column: labels.len(),
+                    attribute: String::new(),
+                };
+                let tokens = tokenize(declaration.as_str()).map_err(unterminated)?;
+                let (label, kind) = match tokens.split_first() {
+                    Some((Token::Word { text, .. }, kind)) => (text.clone(), kind),
+                    _ => {
+                        return Err(ArffError::MissingAttributeName {
+                            line: line_number,
+                            column: labels.len(),
                         })
-                        .collect();
-                    let (class, features) = values.split_last().expect("empty line");
-                    records.push(Record {
-                        class: *class,
-                        features: Vec::from(features),
+                    }
+                };
+                if labels.contains(&label) {
+                    return Err(ArffError::DuplicateAttribute {
+                        line: line_number,
+                        column: labels.len(),
+                        attribute: label,
                     });
+                }
+                labels.push(label);
+                if let [Token::Open, values @ .., Token::Close] = kind {
+                    let class_list: Vec<String> = values
+                        .iter()
+                        .filter_map(|token| match token {
+                            Token::Word { text, .. } => Some(text.clone()),
+                            _ => None,
+                        })
+                        .collect();
+                    classes.push(Class::Nominal(class_list));
                 } else {
-                    // Check if it is an attribute line
-                    if let Some(label) = ATTRIBUTE
-                        .captures(line)
-                        .and_then(|cap| cap.get(1))
-                        .map(|m| String::from(m.as_str().trim()))
-                    {
-                        labels.push(label);
-                        // Check if it is nominal
-                        if let Some(classes_raw) = NOMINAL
-                            .captures(line)
-                            .and_then(|cap| cap.get(1))
-                            .map(|m| String::from(m.as_str()))
-                        {
-                            let mut class_list: Vec<String> = CLASS
-                                .captures_iter(&classes_raw)
-                                .map(|captures| {
-                                    String::from(captures.get(1).unwrap().as_str().trim())
-                                })
-                                .collect();
-                            class_list.push("?".to_owned());
-                            classes.push(Class::Nominal(class_list));
-                        } else {
-                            classes.push(Class::Continuous(0));
+                    let kind: Vec<&str> = kind
+                        .iter()
+                        .filter_map(|token| match token {
+                            Token::Word { text, .. } => Some(text.as_str()),
+                            _ => None,
+                        })
+                        .collect();
+                    // Anything else, such as numeric, real or integer, is a number
+                    classes.push(match kind[..] {
+                        [kind] if kind.eq_ignore_ascii_case("string") => Class::String(Vec::new()),
+                        [kind] if kind.eq_ignore_ascii_case("date") => Class::Date(date::DEFAULT_FORMAT.to_owned()),
+                        [kind, format] if kind.eq_ignore_ascii_case("date") => Class::Date(format.to_owned()),
+                        [first, ..] if first.eq_ignore_ascii_case("string") || first.eq_ignore_ascii_case("date") => {
+                            return Err(ArffError::BadAttributeType {
+                                line: line_number,
+                                column: labels.len() - 1,
+                                attribute: labels[labels.len() - 1].clone(),
+                                value: kind.join(" "),
+                            })
                         }
-                    } else if DATA.is_match(line) {
-                        data_section = true;
-                    }
+                        _ => Class::Continuous,
+                    });
+                }
+            } else if let Some(relation) = RELATION.captures(line.trim()).and_then(|cap| cap.get(1)) {
+                let tokens = tokenize(relation.as_str()).map_err(|_| ArffError::UnterminatedQuote {
+                    line: line_number,
+                    column: 0,
+                    attribute: String::new(),
+                })?;
+                if let Some(Token::Word { text, .. }) = tokens.first() {
+                    reader.relation = text.clone();
+                }
+            } else if DATA.is_match(line.trim_start()) {
+                reader.target = resolve_target(target, labels, line_number)?;
+                return Ok(reader);
+            } else if !HEADER.is_match(line.trim_start()) {
+                return Err(ArffError::DataBeforeDataSection {
+                    line: line_number,
+                    column: 0,
+                    attribute: labels.first().cloned().unwrap_or_default(),
+                });
+            }
+        }
+        // A header without data
+        reader.target = resolve_target(target, &reader.labels, reader.line_number)?;
+        Ok(reader)
+    }
+
+    /// Next line that isn't blank or a comment.
+    fn next_line(&mut self) -> Option<Result<String, ArffError>> {
+        for line in self.lines.by_ref() {
+            self.line_number += 1;
+            match line {
+                Ok(line) if line.starts_with('%') || line.trim().is_empty() => continue,
+                Ok(line) => return Some(Ok(line)),
+                Err(err) => {
+                    return Some(Err(ArffError::Io {
+                        line: self.line_number,
+                        message: err.to_string(),
+                    }))
                 }
             }
         }
+        None
+    }
+}
+
+impl<R: BufRead> Iterator for ArffReader<R> {
+    type Item = Result<Record, ArffError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = match self.next_line()? {
+            Ok(line) => line,
+            Err(err) => return Some(Err(err)),
+        };
+        let line_number = self.line_number;
+        let record = tokenize(&line)
+            .map_err(|column| ArffError::UnterminatedQuote {
+                line: line_number,
+                column,
+                attribute: self.labels.get(column).cloned().unwrap_or_default(),
+            })
+            .and_then(|tokens| {
+                parse_record(
+                    &tokens,
+                    &mut self.classes,
+                    &self.labels,
+                    self.target,
+                    line_number,
+                    &mut self.defaults,
+                )
+            });
+        Some(record)
+    }
+}
+
+impl Arff {
+    /// Reads a whole ARFF file whose class is the `target` attribute.
+    pub fn read<R: BufRead>(reader: R, target: &Target) -> Result<Self, ArffError> {
+        let mut reader = ArffReader::new(reader, target)?;
+        let records = reader.by_ref().collect::<Result<Vec<Record>, ArffError>>()?;
         Ok(Self {
+            relation: reader.relation,
             records,
-            labels,
-            classes,
+            labels: reader.labels,
+            classes: reader.classes,
+            target: reader.target,
+            scaler: None,
+            discretizer: None,
         })
     }
 
+    /// Turns every string attribute into a nominal one over the values seen,
+    /// so trees can learn from it.
+    pub fn strings_to_nominal(&mut self) {
+        let strings: Vec<bool> = self.classes.iter().map(|class| matches!(class, Class::String(_))).collect();
+        let to_nominal = |index: usize, value: Value| match value {
+            Value::Text(text) if strings[index] => Value::Nominal(text),
+            _ => value,
+        };
+        map_features(&mut self.records, to_nominal);
+        for record in self.records.iter_mut() {
+            record.class = to_nominal(self.target, record.class);
+        }
+        for class in self.classes.iter_mut() {
+            if let Class::String(values) = class {
+                *class = Class::Nominal(std::mem::take(values));
+            }
+        }
+    }
+
+    /// Writes the dataset as ARFF. With `predictions`, one per record, every
+    /// record also gets the predicted class and the probability of each class.
+    pub fn write<W: Write>(&self, writer: &mut W, predictions: Option<&[Prediction]>) -> io::Result<()> {
+        write_header(writer, &self.relation, &self.labels, &self.classes, self.target, predictions.is_some())?;
+        for (index, record) in self.records.iter().enumerate() {
+            let prediction = predictions.map(|predictions| &predictions[index]);
+            write_record(writer, record, &self.classes, self.target, prediction)?;
+        }
+        Ok(())
+    }
+
+    /// Checks that records read with attribute types `classes` can be
+    /// conformed to this dataset: every attribute has the same kind, or the
+    /// kind this dataset turned it into.
+    pub fn check_attributes(&self, classes: &[Class]) -> Result<(), String> {
+        let kind = |class: &Class| match class {
+            Class::Nominal(_) => "nominal",
+            Class::Continuous => "numeric",
+            Class::String(_) => "string",
+            Class::Date(_) => "date",
+        };
+        let discretized = |index: usize| {
+            self.discretizer
+                .as_ref()
+                .is_some_and(|discretizer| discretizer.labels(index).is_some())
+        };
+        if classes.len() != self.classes.len() {
+            return Err(format!("expected {} attributes, found {}", self.classes.len(), classes.len()));
+        }
+        for (index, (theirs, own)) in classes.iter().zip(self.classes.iter()).enumerate() {
+            let compatible = match (theirs, own) {
+                (Class::String(_), Class::Nominal(_)) => true,
+                (Class::Continuous, Class::Nominal(_)) => discretized(index),
+                _ => kind(theirs) == kind(own),
+            };
+            if !compatible {
+                return Err(format!(
+                    "attribute {} is {} but {} in the training data",
+                    self.labels[index],
+                    kind(theirs),
+                    kind(own)
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Maps the values of `record`, read with attribute types `classes` that
+    /// passed `check_attributes`, onto this dataset's. Nominal values are
+    /// matched by name, and new strings are added to its string attributes.
+    /// Nominal values this dataset doesn't declare become missing, and so do
+    /// strings of attributes `strings_to_nominal` converted that it didn't see.
+    pub fn conform(&mut self, record: &mut Record, classes: &[Class]) {
+        let own_classes = &mut self.classes;
+        let mut to_nominal = |index: usize, value: Value| match (value, &classes[index], &mut own_classes[index]) {
+            (Value::Nominal(nominal), Class::Nominal(theirs), Class::Nominal(values)) => values
+                .iter()
+                .position(|value| *value == theirs[nominal])
+                .map_or(Value::Missing, Value::Nominal),
+            (Value::Text(text), Class::String(strings), Class::Nominal(values)) => values
+                .iter()
+                .position(|value| *value == strings[text])
+                .map_or(Value::Missing, Value::Nominal),
+            (Value::Text(text), Class::String(strings), Class::String(own_strings)) => {
+                Value::Text(match own_strings.iter().position(|value| *value == strings[text]) {
+                    Some(index) => index,
+                    None => {
+                        own_strings.push(strings[text].clone());
+                        own_strings.len() - 1
+                    }
+                })
+            }
+            _ => value,
+        };
+        record.features.map(&mut to_nominal);
+        record.class = to_nominal(self.target, record.class);
+        self.transform(std::slice::from_mut(record));
+    }
+
+    /// A dataset with the same relation, attributes and fitted preprocessing
+    /// as this one, holding `records` instead.
+    pub fn with_records(&self, records: Vec<Record>) -> Arff {
+        Arff {
+            relation: self.relation.clone(),
+            records,
+            labels: self.labels.clone(),
+            classes: self.classes.clone(),
+            target: self.target,
+            scaler: self.scaler.clone(),
+            discretizer: self.discretizer.clone(),
+        }
+    }
+
+    /// Applies the preprocessing fitted to this dataset to `records` with its
+    /// attribute types from before the preprocessing, such as a test split.
+    pub fn transform(&self, records: &mut [Record]) {
+        if let Some(scaler) = &self.scaler {
+            map_features(records, |index, value| scaler.apply(index, value));
+        }
+        if let Some(discretizer) = &self.discretizer {
+            map_features(records, |index, value| discretizer.apply(index, value));
+        }
+    }
+
+    /// Fits `scaling` to the records, min-max normalization to [0, 1] or
+    /// z-score standardization, applies it to every numeric attribute but the
+    /// class and keeps it for `conform` and `transform`.
+    pub fn scale(&mut self, scaling: Scaling) {
+        let scaler = Scaler::fit(&self.records, &self.classes, self.target, scaling);
+        self.apply_scaler(scaler);
+    }
+
+    /// Applies a scaler fitted to other data, such as a training set, and
+    /// keeps it for `conform`.
+    pub fn apply_scaler(&mut self, scaler: Scaler) {
+        map_features(&mut self.records, |index, value| scaler.apply(index, value));
+        self.scaler = Some(scaler);
+    }
+
+    /// Fits `binning` to the records and replaces every continuous attribute
+    /// but the class with a nominal one of intervals, keeping the cut points
+    /// for `conform`.
+    pub fn discretize(&mut self, binning: Binning) -> Result<(), String> {
+        let discretizer = Discretizer::fit(&self.records, &self.classes, self.target, binning)?;
+        self.apply_discretizer(discretizer);
+        Ok(())
+    }
+
+    /// Applies cut points fitted to other data, such as a training set, and
+    /// keeps them for `conform`.
+    pub fn apply_discretizer(&mut self, discretizer: Discretizer) {
+        map_features(&mut self.records, |index, value| discretizer.apply(index, value));
+        for (index, class) in self.classes.iter_mut().enumerate() {
+            if let Some(labels) = discretizer.labels(index) {
+                *class = Class::Nominal(labels);
+            }
+        }
+        self.discretizer = Some(discretizer);
+    }
+
     pub fn shuffle(&mut self) {
         self.records.as_mut_slice().shuffle(&mut thread_rng());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str, quoted: bool) -> Token {
+        Token::Word {
+            text: text.to_owned(),
+            quoted,
+        }
+    }
+
+    #[test]
+    fn tokenize_separators() {
+        assert_eq!(
+            tokenize("a, b ,c\td").unwrap(),
+            vec![word("a", false), word("b", false), word("c", false), word("d", false)]
+        );
+        assert_eq!(tokenize(",, a,,b ").unwrap(), vec![word("a", false), word("b", false)]);
+        assert_eq!(tokenize("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn tokenize_quotes() {
+        assert_eq!(
+            tokenize(r#"'new york', "a, b",'{c}'"#).unwrap(),
+            vec![word("new york", true), word("a, b", true), word("{c}", true)]
+        );
+        assert_eq!(tokenize(r#"'say "hi"'"#).unwrap(), vec![word(r#"say "hi""#, true)]);
+        assert_eq!(tokenize("''").unwrap(), vec![word("", true)]);
+        // Only an unquoted ? is missing, so the quotes are kept apart
+        assert_eq!(tokenize("'?', ?").unwrap(), vec![word("?", true), word("?", false)]);
+    }
+
+    #[test]
+    fn tokenize_escapes() {
+        assert_eq!(
+            tokenize(r"'it\'s' 'a\\b' 'tab\there' one\ word").unwrap(),
+            vec![word("it's", true), word(r"a\b", true), word("tab\there", true), word("one word", false)]
+        );
+        assert_eq!(tokenize(r#""\"hi\"""#).unwrap(), vec![word(r#""hi""#, true)]);
+        assert_eq!(tokenize(r"a\,b").unwrap(), vec![word("a,b", false)]);
+    }
+
+    #[test]
+    fn tokenize_braces() {
+        assert_eq!(
+            tokenize("{0 x,2 'y z'}, {3}").unwrap(),
+            vec![
+                Token::Open,
+                word("0", false),
+                word("x", false),
+                word("2", false),
+                word("y z", true),
+                Token::Close,
+                Token::Open,
+                word("3", false),
+                Token::Close,
+            ]
+        );
+        assert_eq!(tokenize("{a,b}").unwrap(), vec![Token::Open, word("a", false), word("b", false), Token::Close]);
+    }
+
+    #[test]
+    fn tokenize_unterminated() {
+        assert_eq!(tokenize("a, 'b"), Err(1));
+        assert_eq!(tokenize(r"'a\'"), Err(0));
+        assert_eq!(tokenize(r#"a, b, "c"#), Err(2));
+    }
+
+    #[test]
+    fn attribute_without_name() {
+        let header = "@relation r\n@attribute x numeric\n@attribute {a,b}\n@data\n";
+        match ArffReader::new(header.as_bytes(), &Target::Last) {
+            Err(ArffError::MissingAttributeName { line, column }) => assert_eq!((line, column), (3, 1)),
+            other => panic!("expected a missing attribute name, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn unquoted_date_format() {
+        let header = "@relation r\n@attribute d date yyyy-MM-dd HH:mm\n@data\n";
+        match ArffReader::new(header.as_bytes(), &Target::Last) {
+            Err(ArffError::BadAttributeType { line, attribute, value, .. }) => {
+                assert_eq!((line, attribute.as_str(), value.as_str()), (2, "d", "date yyyy-MM-dd HH:mm"))
+            }
+            other => panic!("expected a bad attribute type, got {:?}", other.err()),
+        }
+        let header = "@relation r\n@attribute d date 'yyyy-MM-dd HH:mm'\n@attribute s string\n@data\n";
+        assert!(ArffReader::new(header.as_bytes(), &Target::Last).is_ok());
+    }
+
+    fn parse(line: &str, classes: &mut [Class], defaults: &mut Option<Arc<Vec<Value>>>) -> Result<Record, ArffError> {
+        let labels = vec![String::from("x"), String::from("y"), String::from("class")];
+        parse_record(&tokenize(line).unwrap(), classes, &labels, 2, 1, defaults)
+    }
+
+    fn sample_classes() -> Vec<Class> {
+        vec![
+            Class::Nominal(vec![String::from("a"), String::from("b")]),
+            Class::Continuous,
+            Class::Nominal(vec![String::from("yes"), String::from("no")]),
+        ]
+    }
+
+    #[test]
+    fn dense_record_weight() {
+        let mut classes = sample_classes();
+        let record = parse("b, 2.5, no, {0.5}", &mut classes, &mut None).unwrap();
+        assert_eq!(record.features[0], Value::Nominal(1));
+        assert_eq!(record.features[1], Value::Continuous(2.5));
+        assert_eq!(record.class, Value::Nominal(1));
+        assert_eq!(record.weight, 0.5);
+        assert_eq!(parse("a, 1, yes", &mut classes, &mut None).unwrap().weight, 1.);
+        match parse("a, 1, yes, {-1}", &mut classes, &mut None) {
+            Err(ArffError::BadNumber { attribute, value, .. }) => {
+                assert_eq!((attribute.as_str(), value.as_str()), ("weight", "-1"))
+            }
+            other => panic!("expected a bad weight, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sparse_record_defaults() {
+        let mut classes = sample_classes();
+        let mut defaults = None;
+        let record = parse("{2 no, 1 2.5}, {3}", &mut classes, &mut defaults).unwrap();
+        assert_eq!(record.features[0], Value::Nominal(0));
+        assert_eq!(record.features[1], Value::Continuous(2.5));
+        assert_eq!(record.class, Value::Nominal(1));
+        assert_eq!(record.weight, 3.);
+        // The class takes its default when left out too
+        let other = parse("{0 b}", &mut classes, &mut defaults).unwrap();
+        assert_eq!(other.features[0], Value::Nominal(1));
+        assert_eq!(other.features[1], Value::Continuous(0.));
+        assert_eq!(other.class, Value::Nominal(0));
+        assert_eq!(other.weight, 1.);
+        // Every sparse row shares the same defaults
+        match (&record.features, &other.features) {
+            (Features::Sparse { defaults: first, .. }, Features::Sparse { defaults: second, .. }) => {
+                assert!(Arc::ptr_eq(first, second))
+            }
+            _ => panic!("expected sparse records"),
+        }
+    }
+
+    #[test]
+    fn sparse_record_bad_entry() {
+        let mut classes = sample_classes();
+        for line in ["{3 a}", "{x a}", "{0}"] {
+            match parse(line, &mut classes, &mut None) {
+                Err(ArffError::BadSparseEntry { .. }) => {}
+                other => panic!("expected a bad sparse entry for {}, got {:?}", line, other),
+            }
+        }
+    }
+
+    #[test]
+    fn write_then_read() {
+        let source = "@relation 'round trip'
+@attribute 'home town' {'new york', paris}
+@attribute size numeric
+@attribute note string
+@attribute seen date 'yyyy-MM-dd HH:mm'
+@attribute class {yes, no}
+@data
+'new york', 1.5, 'it\\'s', '2024-02-29 13:45', yes
+paris, ?, ?, ?, no, {2.5}
+{1 -3, 2 'a, b', 4 no}
+{0 paris}, {0.5}
+";
+        let arff = Arff::read(source.as_bytes(), &Target::Last).unwrap();
+        let mut written = Vec::new();
+        write_header(&mut written, &arff.relation, &arff.labels, &arff.classes, arff.target, false).unwrap();
+        for record in arff.records.iter() {
+            write_record(&mut written, record, &arff.classes, arff.target, None).unwrap();
+        }
+        let read = Arff::read(written.as_slice(), &Target::Last).unwrap();
+        assert_eq!(read.relation, arff.relation);
+        assert_eq!(read.labels, arff.labels);
+        assert_eq!(format!("{:?}", read.classes), format!("{:?}", arff.classes));
+        assert_eq!(read.records.len(), 4);
+        for (read, record) in read.records.iter().zip(arff.records.iter()) {
+            for index in 0..arff.classes.len() {
+                assert_eq!(read.features[index], record.features[index]);
+            }
+            assert_eq!(read.class, record.class);
+            assert_eq!(read.weight, record.weight);
+            assert_eq!(
+                matches!(read.features, Features::Sparse { .. }),
+                matches!(record.features, Features::Sparse { .. })
+            );
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use std::error::Error;
+use std::fmt;
+
+/// Cost of predicting one class when the record belongs to another, indexed
+/// `[actual][predicted]`. Pairs the cost file leaves out cost 0 when the
+/// prediction is right and 1 otherwise.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostMatrix {
+    costs: Vec<Vec<f64>>,
+}
+
+/// Every variant carries the 1-based line number the problem was found on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CostError {
+    /// The line doesn't have the form `actual predicted cost`
+    Malformed { line: usize },
+    UnknownClass { line: usize, value: String },
+    BadCost { line: usize, value: String },
+}
+
+impl fmt::Display for CostError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CostError::Malformed { line } => {
+                write!(f, "line {}: expected <actual> <predicted> <cost>", line)
+            }
+            CostError::UnknownClass { line, value } => {
+                write!(f, "line {}: unknown class value {:?}", line, value)
+            }
+            CostError::BadCost { line, value } => {
+                write!(f, "line {}: {:?} is not a cost", line, value)
+            }
+        }
+    }
+}
+
+impl Error for CostError {}
+
+impl CostMatrix {
+    /// Zero-one costs, under which the cheapest class is the majority class.
+    pub fn zero_one(class_count: usize) -> Self {
+        let costs = (0..class_count)
+            .map(|actual| (0..class_count).map(|predicted| if actual == predicted { 0. } else { 1. }).collect())
+            .collect();
+        Self { costs }
+    }
+
+    /// Reads one `actual predicted cost` entry per line, naming classes by
+    /// their values in `class_values`. Values may be separated by commas or
+    /// whitespace, and lines starting with `%` are comments.
+    pub fn parse(contents: &str, class_values: &[String]) -> Result<Self, CostError> {
+        let mut matrix = Self::zero_one(class_values.len());
+        let class_index = |line: usize, value: &str| {
+            class_values
+                .iter()
+                .position(|class| class == value)
+                .ok_or_else(|| CostError::UnknownClass {
+                    line,
+                    value: value.to_owned(),
+                })
+        };
+        for (line_index, line) in contents.lines().enumerate() {
+            let line_number = line_index + 1;
+            if line.starts_with('%') || line.trim().is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|field| !field.is_empty())
+                .collect();
+            if let [actual, predicted, cost] = fields[..] {
+                let actual = class_index(line_number, actual)?;
+                let predicted = class_index(line_number, predicted)?;
+                matrix.costs[actual][predicted] = cost
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|cost| *cost >= 0.)
+                    .ok_or_else(|| CostError::BadCost {
+                        line: line_number,
+                        value: cost.to_owned(),
+                    })?;
+            } else {
+                return Err(CostError::Malformed { line: line_number });
+            }
+        }
+        Ok(matrix)
+    }
+
+    pub fn cost(&self, actual: usize, predicted: usize) -> f64 {
+        self.costs[actual][predicted]
+    }
+
+    /// Expected cost of predicting each class for records distributed as
+    /// `distribution`.
+    pub fn expected_costs(&self, distribution: &[f64]) -> Vec<f64> {
+        (0..self.costs.len())
+            .map(|predicted| {
+                distribution
+                    .iter()
+                    .enumerate()
+                    .map(|(actual, weight)| weight * self.costs[actual][predicted])
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// Per-class instance weights proportional to the total cost of
+    /// misclassifying the class, scaled so the training weight `prior` sums
+    /// to the same total (Ting, 2002).
+    pub fn class_weights(&self, prior: &[f64]) -> Vec<f64> {
+        let misclassification: Vec<f64> = self.costs.iter().map(|row| row.iter().sum()).collect();
+        let total: f64 = prior.iter().sum();
+        let weighted_total: f64 = prior.iter().zip(misclassification.iter()).map(|(n, c)| n * c).sum();
+        if weighted_total == 0. {
+            return vec![1.; prior.len()];
+        }
+        misclassification.iter().map(|cost| cost * total / weighted_total).collect()
+    }
+}
@@ -0,0 +1,80 @@
+use std::str::FromStr;
+
+/// Impurity measure of a class distribution, used to score candidate splits.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Criterion {
+    /// Shannon entropy in bits (ID3/C4.5)
+    #[default]
+    Entropy,
+    /// Gini impurity (CART)
+    Gini,
+    /// Fraction of records outside the majority class
+    Misclassification,
+}
+
+impl Criterion {
+    pub fn impurity(self, distribution: &[f64]) -> f64 {
+        let total_size = distribution.iter().sum::<f64>();
+        if total_size == 0. {
+            return 0.;
+        }
+        match self {
+            Criterion::Entropy => -calculate_information(distribution, total_size),
+            Criterion::Gini => {
+                1. - distribution
+                    .iter()
+                    .map(|count| count / total_size)
+                    .map(|frac| frac * frac)
+                    .sum::<f64>()
+            }
+            Criterion::Misclassification => {
+                1. - distribution.iter().cloned().fold(0., f64::max) / total_size
+            }
+        }
+    }
+
+    /// Impurity left after splitting a node of `total_size` records into
+    /// children with the given class distributions, weighted by child size.
+    pub fn remaining(self, distributions: &[Vec<f64>], total_size: f64) -> f64 {
+        distributions
+            .iter()
+            .map(|distribution| {
+                let child_size = distribution.iter().sum::<f64>();
+                self.impurity(distribution) * (child_size / total_size)
+            })
+            .sum()
+    }
+
+    pub fn gain(self, parent: &[f64], distributions: &[Vec<f64>]) -> f64 {
+        let total_size = parent.iter().sum::<f64>();
+        self.impurity(parent) - self.remaining(distributions, total_size)
+    }
+}
+
+impl FromStr for Criterion {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "entropy" => Ok(Criterion::Entropy),
+            "gini" => Ok(Criterion::Gini),
+            "misclassification" => Ok(Criterion::Misclassification),
+            _ => Err(format!("unknown criterion {}", name)),
+        }
+    }
+}
+
+fn calculate_information(distribution: &[f64], total_size: f64) -> f64 {
+    distribution
+        .iter()
+        .filter(|val| **val > 0.)
+        .map(|val| val / total_size)
+        .map(|frac| frac * frac.log2())
+        .fold(0., |acc, v| acc + v)
+}
+
+/// Entropy of the split itself, how evenly the records spread over children
+/// of the given sizes. Gain ratio divides by it whatever the impurity criterion.
+pub fn split_information(child_sizes: &[f64], total_size: f64) -> f64 {
+    -calculate_information(child_sizes, total_size)
+}
@@ -0,0 +1,256 @@
+use crate::arff::{resolve_target, Arff, ArffError, Class, Target};
+use crate::record::{Features, Record, Value};
+use std::collections::HashSet;
+use std::io::BufRead;
+
+/// Type forced on a CSV column instead of the inferred one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnKind {
+    Nominal,
+    Numeric,
+}
+
+/// How `read` infers the attribute type of every column.
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    /// Columns of whole numbers with at most this many distinct values are
+    /// nominal, like codes and counts
+    pub max_nominal_values: usize,
+    /// Column names with a fixed type
+    pub overrides: Vec<(String, ColumnKind)>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            max_nominal_values: 10,
+            overrides: Vec::new(),
+        }
+    }
+}
+
+/// Line a row starts on and its cells.
+type Row = (usize, Vec<String>);
+
+/// Splits `contents` into rows of cells. Cells may be quoted with `"`, which
+/// allows commas and line breaks in them, and `""` is a quote inside one.
+/// Fails with the line and column a quote is left open on.
+fn rows(contents: &str) -> Result<Vec<Row>, (usize, usize)> {
+    let mut rows = Vec::new();
+    let mut chars = contents.chars().peekable();
+    let mut line = 1;
+    while chars.peek().is_some() {
+        let row_line = line;
+        let mut row = Vec::new();
+        let mut cell = String::new();
+        loop {
+            match chars.next() {
+                Some('"') => {
+                    let quote_line = line;
+                    loop {
+                        match chars.next() {
+                            Some('"') if chars.next_if_eq(&'"').is_some() => cell.push('"'),
+                            Some('"') => break,
+                            Some(c) => {
+                                if c == '\n' {
+                                    line += 1;
+                                }
+                                cell.push(c);
+                            }
+                            None => return Err((quote_line, row.len())),
+                        }
+                    }
+                }
+                Some(',') => row.push(std::mem::take(&mut cell)),
+                Some('\r') if chars.peek() == Some(&'\n') => {}
+                Some('\n') | None => {
+                    line += 1;
+                    row.push(cell);
+                    break;
+                }
+                Some(c) => cell.push(c),
+            }
+        }
+        // Blank lines carry no record
+        if row.len() > 1 || !row[0].trim().is_empty() {
+            rows.push((row_line, row));
+        }
+    }
+    Ok(rows)
+}
+
+fn is_missing(cell: &str) -> bool {
+    cell.is_empty() || cell == "?"
+}
+
+/// Attribute type of the cells of one column.
+fn infer(cells: &[&str], kind: Option<ColumnKind>, options: &CsvOptions) -> Class {
+    let known: Vec<&str> = cells.iter().cloned().filter(|cell| !is_missing(cell)).collect();
+    let numbers: Option<Vec<f64>> = known.iter().map(|cell| cell.parse::<f64>().ok()).collect();
+    let mut values: Vec<String> = Vec::new();
+    let mut seen = HashSet::new();
+    for cell in known.iter() {
+        if seen.insert(*cell) {
+            values.push(cell.to_string());
+        }
+    }
+    let kind = kind.unwrap_or(match numbers {
+        Some(numbers)
+            if numbers.iter().any(|number| number.fract() != 0.)
+                || values.len() > options.max_nominal_values =>
+        {
+            ColumnKind::Numeric
+        }
+        Some(_) if values.is_empty() => ColumnKind::Numeric,
+        _ => ColumnKind::Nominal,
+    });
+    match kind {
+        ColumnKind::Nominal => Class::Nominal(values),
+        ColumnKind::Numeric => Class::Continuous,
+    }
+}
+
+/// Reads a CSV file whose first row names the columns into an `Arff` with
+/// the `target` column as the class. Empty cells and `?` are missing.
+pub fn read<R: BufRead>(mut reader: R, target: &Target, options: &CsvOptions) -> Result<Arff, ArffError> {
+    let mut contents = String::new();
+    reader.read_to_string(&mut contents).map_err(|err| ArffError::Io {
+        line: 0,
+        message: err.to_string(),
+    })?;
+    let rows = rows(&contents).map_err(|(line, column)| ArffError::UnterminatedQuote {
+        line,
+        column,
+        attribute: String::new(),
+    })?;
+    let (header, rows) = match rows.split_first() {
+        Some(((_, header), rows)) => (header, rows),
+        None => (&Vec::new(), &[][..]),
+    };
+    let labels: Vec<String> = header.iter().map(|label| label.trim().to_owned()).collect();
+    let cells: Vec<(usize, Vec<&str>)> = rows
+        .iter()
+        .map(|(line, row)| (*line, row.iter().map(|cell| cell.trim()).collect()))
+        .collect();
+    if let Some((line, row)) = cells.iter().find(|(_, row)| row.len() != labels.len()) {
+        let column = row.len().min(labels.len());
+        return Err(ArffError::WrongColumnCount {
+            line: *line,
+            column,
+            attribute: labels.get(column).cloned().unwrap_or_default(),
+            expected: labels.len(),
+            found: row.len(),
+        });
+    }
+    let classes: Vec<Class> = labels
+        .iter()
+        .enumerate()
+        .map(|(column, label)| {
+            let column_cells: Vec<&str> = cells.iter().map(|(_, row)| row[column]).collect();
+            let kind = options
+                .overrides
+                .iter()
+                .find(|(name, _)| name == label)
+                .map(|(_, kind)| *kind);
+            infer(&column_cells, kind, options)
+        })
+        .collect();
+    let target = resolve_target(target, &labels, 1)?;
+    let records = cells
+        .into_iter()
+        .map(|(line, row)| {
+            let values = row
+                .into_iter()
+                .zip(classes.iter())
+                .enumerate()
+                .map(|(column, (cell, class))| match class {
+                    _ if is_missing(cell) => Ok(Value::Missing),
+                    Class::Nominal(values) => Ok(Value::Nominal(values.iter().position(|value| value == cell).unwrap())),
+                    _ => cell
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|value| value.is_finite())
+                        .map(Value::Continuous)
+                        .ok_or_else(|| ArffError::BadNumber {
+                            line,
+                            column,
+                            attribute: labels[column].clone(),
+                            value: cell.to_owned(),
+                        }),
+                })
+                .collect::<Result<Vec<Value>, ArffError>>()?;
+            Ok(Record {
+                class: values[target],
+                features: Features::Dense(values),
+                weight: 1.,
+            })
+        })
+        .collect::<Result<Vec<Record>, ArffError>>()?;
+    Ok(Arff {
+        relation: String::new(),
+        records,
+        labels,
+        classes,
+        target,
+        scaler: None,
+        discretizer: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(row: &[&str]) -> Vec<String> {
+        row.iter().map(|cell| cell.to_string()).collect()
+    }
+
+    #[test]
+    fn quoted_cells() {
+        assert_eq!(
+            rows("a,\"b, c\",\"say \"\"hi\"\"\"\r\n\"two\nlines\",,x\n").unwrap(),
+            vec![(1, cells(&["a", "b, c", "say \"hi\""])), (2, cells(&["two\nlines", "", "x"]))]
+        );
+        assert_eq!(rows("a,\"b\nc").unwrap_err(), (1, 1));
+    }
+
+    #[test]
+    fn blank_lines() {
+        assert_eq!(rows("a,b\n\n  \n1,2").unwrap(), vec![(1, cells(&["a", "b"])), (4, cells(&["1", "2"]))]);
+        assert_eq!(rows("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn inferred_types() {
+        let options = CsvOptions {
+            max_nominal_values: 2,
+            ..CsvOptions::default()
+        };
+        let nominal = |values: &[&str]| format!("{:?}", Class::Nominal(cells(values)));
+        let inferred = |column: &[&str], kind| format!("{:?}", infer(column, kind, &options));
+        // Few whole numbers are codes, missing cells aside
+        assert_eq!(inferred(&["1", "?", "2", "", "1"], None), nominal(&["1", "2"]));
+        assert_eq!(inferred(&["1", "2", "3"], None), "Continuous");
+        assert_eq!(inferred(&["1", "2.5"], None), "Continuous");
+        assert_eq!(inferred(&["?", ""], None), "Continuous");
+        assert_eq!(inferred(&["red", "1", "red"], None), nominal(&["red", "1"]));
+        assert_eq!(inferred(&["1", "2", "3"], Some(ColumnKind::Nominal)), nominal(&["1", "2", "3"]));
+        assert_eq!(inferred(&["1", "2"], Some(ColumnKind::Numeric)), "Continuous");
+    }
+
+    #[test]
+    fn missing_cells() {
+        let arff = read("x,y,class\n1.5,,a\n?,red,b\n".as_bytes(), &Target::Last, &CsvOptions::default()).unwrap();
+        assert_eq!(arff.labels, cells(&["x", "y", "class"]));
+        assert_eq!(arff.records[0].features[1], Value::Missing);
+        assert_eq!(arff.records[1].features[0], Value::Missing);
+        assert_eq!(arff.records[1].features[1], Value::Nominal(0));
+        assert_eq!(arff.records[1].class, Value::Nominal(1));
+        match read("x,class\n1,a\n2\n".as_bytes(), &Target::Last, &CsvOptions::default()) {
+            Err(ArffError::WrongColumnCount { line, expected, found, .. }) => {
+                assert_eq!((line, expected, found), (3, 2, 1))
+            }
+            other => panic!("expected a wrong column count, got {:?}", other.err()),
+        }
+    }
+}
@@ -0,0 +1,234 @@
+/// Format of ARFF dates without an explicit one, ISO-8601.
+pub const DEFAULT_FORMAT: &str = "yyyy-MM-dd'T'HH:mm:ss";
+
+/// Latest year `parse` reads, far enough out that its seconds and
+/// milliseconds fit in an `i64`.
+const MAX_YEAR: i64 = 999_999;
+
+/// A piece of a `SimpleDateFormat` style pattern.
+#[derive(Debug, PartialEq)]
+enum Segment {
+    /// A pattern letter and how many times it is repeated, like `yyyy`
+    Field(char, usize),
+    Literal(String),
+}
+
+fn segments(format: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\'' {
+            // Quoted literal text, `''` is a quote
+            let mut text = String::new();
+            while let Some(next) = chars.next() {
+                if next == '\'' {
+                    if chars.peek() == Some(&'\'') {
+                        text.push(chars.next().unwrap());
+                    } else {
+                        break;
+                    }
+                } else {
+                    text.push(next);
+                }
+            }
+            if text.is_empty() {
+                text.push('\'');
+            }
+            segments.push(Segment::Literal(text));
+        } else if c.is_ascii_alphabetic() {
+            let mut count = 1;
+            while chars.next_if_eq(&c).is_some() {
+                count += 1;
+            }
+            segments.push(Segment::Field(c, count));
+        } else {
+            segments.push(Segment::Literal(c.to_string()));
+        }
+    }
+    segments
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days from 1970-01-01 to the given day of the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Seconds since the Unix epoch, in UTC, of `value` read with a
+/// `SimpleDateFormat` style `format`. Supports the numeric fields `y`, `M`,
+/// `d`, `H`, `m`, `s` and `S` and literal text, and fails on any other
+/// letter, on days the month doesn't have and on years past `MAX_YEAR`.
+pub fn parse(value: &str, format: &str) -> Option<f64> {
+    let segments = segments(format);
+    // Month names and letters without a numeric meaning here
+    let unsupported = |segment: &Segment| match segment {
+        Segment::Field('M', count) => *count > 2,
+        Segment::Field(letter, _) => !"yMdHmsS".contains(*letter),
+        Segment::Literal(_) => false,
+    };
+    if segments.iter().any(unsupported) {
+        return None;
+    }
+    let (mut year, mut month, mut day) = (1970, 1, 1);
+    let (mut hour, mut minute, mut second, mut millisecond) = (0, 0, 0, 0);
+    let mut rest = value;
+    for (index, segment) in segments.iter().enumerate() {
+        match segment {
+            Segment::Literal(text) => rest = rest.strip_prefix(text.as_str())?,
+            Segment::Field(letter, count) => {
+                // Fields right next to another field take exactly their width
+                let width = match segments.get(index + 1) {
+                    Some(Segment::Field(..)) => *count,
+                    _ => rest.len(),
+                };
+                let digits = rest
+                    .char_indices()
+                    .take(width)
+                    .take_while(|(_, c)| c.is_ascii_digit())
+                    .last()
+                    .map_or(0, |(position, c)| position + c.len_utf8());
+                let number: i64 = rest[..digits].parse().ok()?;
+                rest = &rest[digits..];
+                match letter {
+                    'y' if number <= MAX_YEAR => year = number,
+                    'M' if (1..=12).contains(&number) => month = number,
+                    'd' if (1..=31).contains(&number) => day = number,
+                    'H' if number < 24 => hour = number,
+                    'm' if number < 60 => minute = number,
+                    's' if number < 61 => second = number,
+                    'S' => millisecond = number,
+                    _ => return None,
+                }
+            }
+        }
+    }
+    if !rest.is_empty() || day > days_in_month(year, month) {
+        return None;
+    }
+    let seconds = days_from_civil(year, month, day) * 86_400 + hour * 3_600 + minute * 60 + second;
+    Some(seconds as f64 + millisecond as f64 / 1000.)
+}
+
+/// Year, month and day of the day `days` after 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Writes `seconds` since the Unix epoch in UTC with a format `parse` reads.
+pub fn format(seconds: f64, format: &str) -> String {
+    let milliseconds = (seconds * 1000.).round() as i64;
+    let (days, milliseconds) = (milliseconds.div_euclid(86_400_000), milliseconds.rem_euclid(86_400_000));
+    let (year, month, day) = civil_from_days(days);
+    let seconds = milliseconds / 1000;
+    segments(format)
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Literal(text) => text,
+            Segment::Field(letter, count) => {
+                let number = match letter {
+                    'y' => year,
+                    'M' => month,
+                    'd' => day,
+                    'H' => seconds / 3_600,
+                    'm' => seconds / 60 % 60,
+                    's' => seconds % 60,
+                    'S' => milliseconds % 1000,
+                    _ => 0,
+                };
+                format!("{:0width$}", number, width = count)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn epoch() {
+        assert_eq!(parse("1970-01-01T00:00:00", DEFAULT_FORMAT), Some(0.));
+        assert_eq!(format(0., DEFAULT_FORMAT), "1970-01-01T00:00:00");
+        assert_eq!(parse("2001-09-09T01:46:40", DEFAULT_FORMAT), Some(1_000_000_000.));
+    }
+
+    #[test]
+    fn round_trips() {
+        let format_ms = "yyyy-MM-dd HH:mm:ss.SSS";
+        for value in ["2024-02-29 23:59:59.999", "1969-12-31 23:59:59.500", "1900-03-01 12:00:00.000"] {
+            let seconds = parse(value, format_ms).unwrap();
+            assert_eq!(format(seconds, format_ms), value);
+        }
+        for seconds in [-86_400. * 365_250., -1., 0., 951_782_400., 4_102_444_800.] {
+            assert_eq!(parse(&format(seconds, DEFAULT_FORMAT), DEFAULT_FORMAT), Some(seconds));
+        }
+    }
+
+    #[test]
+    fn negative_epochs() {
+        assert_eq!(parse("1969-12-31T23:59:59", DEFAULT_FORMAT), Some(-1.));
+        assert_eq!(parse("1969-12-31", "yyyy-MM-dd"), Some(-86_400.));
+        assert_eq!(format(-86_400. * 365., "yyyy-MM-dd"), "1969-01-01");
+        assert_eq!(parse("1600-01-01", "yyyy-MM-dd"), Some(-11_676_096_000.));
+    }
+
+    #[test]
+    fn leap_days() {
+        assert_eq!(parse("2000-02-29", "yyyy-MM-dd"), Some(951_782_400.));
+        assert_eq!(format(951_782_400., "yyyy-MM-dd"), "2000-02-29");
+        assert_eq!(parse("2024-03-01", "yyyy-MM-dd").unwrap() - parse("2024-02-28", "yyyy-MM-dd").unwrap(), 2. * 86_400.);
+        assert_eq!(parse("1900-02-29", "yyyy-MM-dd"), None);
+        assert_eq!(parse("2023-02-29", "yyyy-MM-dd"), None);
+        assert_eq!(parse("2024-02-30", "yyyy-MM-dd"), None);
+        assert_eq!(parse("2024-04-31", "yyyy-MM-dd"), None);
+    }
+
+    #[test]
+    fn adjacent_fields() {
+        assert_eq!(parse("20240229", "yyyyMMdd"), parse("2024-02-29", "yyyy-MM-dd"));
+        assert_eq!(format(parse("20240229", "yyyyMMdd").unwrap(), "yyyyMMdd"), "20240229");
+    }
+
+    #[test]
+    fn rejected() {
+        assert_eq!(parse("2024-13-01", "yyyy-MM-dd"), None);
+        assert_eq!(parse("2024-01-01T24:00:00", DEFAULT_FORMAT), None);
+        assert_eq!(parse("2024-01-01 extra", "yyyy-MM-dd"), None);
+        assert_eq!(parse("2024/01/01", "yyyy-MM-dd"), None);
+        // Month names, the am/pm marker, 12-hour clocks and zones
+        assert_eq!(parse("2024-Feb-01", "yyyy-MMM-dd"), None);
+        assert_eq!(parse("2024-02-01", "yyyy-MMM-dd"), None);
+        assert_eq!(parse("2024-02-01 05 PM", "yyyy-MM-dd hh a"), None);
+        assert_eq!(parse("2024-02-01 05", "yyyy-MM-dd hh"), None);
+        assert_eq!(parse("2024-02-01 UTC", "yyyy-MM-dd z"), None);
+        // Years whose seconds would overflow
+        assert_eq!(parse("999999999999999999-01-01", "yyyy-MM-dd"), None);
+        assert_eq!(format(parse("999999-12-31", "yyyy-MM-dd").unwrap(), "yyyy-MM-dd"), "999999-12-31");
+    }
+}
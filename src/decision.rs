@@ -1,17 +1,127 @@
-use crate::record::Record;
+use crate::cost::CostMatrix;
+use crate::criterion::{split_information, Criterion};
+use crate::record::{Record, Value};
+use crate::regression::{LeafPrediction, RegressionCriterion};
+use crate::surrogate::{find_surrogates, Surrogate};
 use itertools::Itertools;
 use std::collections::HashMap;
+use std::str::FromStr;
 
 #[derive(Debug, Default)]
 struct BranchNode {
     paths: HashMap<usize, usize>,
     feature: usize,
+    /// Continuous features split in two: path 0 takes `value <= threshold`
+    /// and path 1 the rest. Nominal features have one path per value.
+    threshold: Option<f64>,
     majority_class: usize,
+    /// Training weight per class that reached the node
+    distribution: Vec<f64>,
+    /// Best first
+    surrogates: Vec<Surrogate>,
+}
+
+impl BranchNode {
+    fn path_key(&self, value: Value) -> Option<usize> {
+        path_key(self.threshold, value)
+    }
+
+    /// Path `record` takes, through the surrogates when its own value is
+    /// missing or has no path.
+    fn follow(&self, record: &Record) -> Option<usize> {
+        let has_path = |path: &usize| self.paths.contains_key(path);
+        self.path_key(record.features[self.feature])
+            .filter(has_path)
+            .or_else(|| {
+                self.surrogates
+                    .iter()
+                    .filter_map(|surrogate| surrogate.primary_path(record))
+                    .find(has_path)
+            })
+            .map(|path| self.paths[&path])
+    }
+}
+
+pub(crate) fn describe_path(threshold: Option<f64>, path: usize, class_tag: &Class) -> String {
+    match threshold {
+        Some(threshold) if path == 0 => format!("<= {}", threshold),
+        Some(threshold) => format!("> {}", threshold),
+        None => match class_tag {
+            Class::Nominal(list) => list[path].clone(),
+            _ => format!("{}", path),
+        },
+    }
+}
+
+/// A node as `print_levels` shows it.
+pub(crate) enum PrintedNode<'a> {
+    Branch {
+        feature: usize,
+        threshold: Option<f64>,
+        paths: &'a HashMap<usize, usize>,
+    },
+    /// The leaf's prediction
+    Leaf(String),
+}
+
+/// Prints a tree one level per line, down to `depth` levels, with `node`
+/// describing the node at an index. Every node shows the feature and value
+/// of the path that lead to it.
+pub(crate) fn print_levels<'a>(
+    depth: usize,
+    class_tags: &[Class],
+    labels: &[String],
+    node: impl Fn(usize) -> PrintedNode<'a>,
+) {
+    let mut current_depth = 0;
+    let root_value = describe_path(None, 0, &class_tags[0]);
+    let mut stack: Vec<((String, usize), usize)> = vec![((root_value, 0), 0)];
+    loop {
+        let mut next_stack = Vec::new();
+        for ((previous_value, previous_feature), node_index) in stack.drain(..) {
+            match node(node_index) {
+                PrintedNode::Branch { feature, threshold, paths } => {
+                    print!("b({}: {}, {}) | ", labels[previous_feature], previous_value, labels[feature]);
+                    for (k, v) in paths.iter() {
+                        let value = describe_path(threshold, *k, &class_tags[feature]);
+                        next_stack.push(((value, feature), *v));
+                    }
+                }
+                PrintedNode::Leaf(prediction) => {
+                    print!("l({}: {}, {}) | ", labels[previous_feature], previous_value, prediction);
+                }
+            }
+        }
+        println!();
+        current_depth += 1;
+        if next_stack.is_empty() || current_depth >= depth {
+            break;
+        }
+        stack = next_stack;
+    }
+}
+
+/// Path a value takes out of a branch, `None` when the value is missing.
+pub(crate) fn path_key(threshold: Option<f64>, value: Value) -> Option<usize> {
+    match (threshold, value) {
+        (Some(threshold), Value::Continuous(value)) => {
+            if value <= threshold {
+                Some(0)
+            } else {
+                Some(1)
+            }
+        }
+        (_, Value::Nominal(index)) => Some(index),
+        (_, Value::Missing) => None,
+        (_, Value::Text(_)) => unreachable!("string features are never split on"),
+        (None, Value::Continuous(_)) => unreachable!("continuous features split on a threshold"),
+    }
 }
 
 #[derive(Debug, Default)]
 struct LeafNode {
     class: usize,
+    distribution: Vec<f64>,
 }
 
 #[derive(Debug)]
@@ -20,198 +130,890 @@ enum Node {
     Leaf(LeafNode),
 }
 
+impl TreeNode for Node {
+    fn paths_mut(&mut self) -> Option<&mut HashMap<usize, usize>> {
+        match self {
+            Node::Branch(branch) => Some(&mut branch.paths),
+            Node::Leaf(_) => None,
+        }
+    }
+}
+
+impl Node {
+    fn distribution(&self) -> &[f64] {
+        match self {
+            Node::Branch(branch) => &branch.distribution,
+            Node::Leaf(leaf) => &leaf.distribution,
+        }
+    }
+}
+
+/// Smoothing of the class counts behind `DecisionTree::predict_proba`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Smoothing {
+    /// Plain relative frequencies
+    #[default]
+    None,
+    /// One extra record of every class
+    Laplace,
+    /// `m` extra records spread by the training prior
+    MEstimate(f64),
+}
+
+impl FromStr for Smoothing {
+    type Err = String;
+
+    /// `none`, `laplace` or `m=<m>`
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "none" => Ok(Smoothing::None),
+            "laplace" => Ok(Smoothing::Laplace),
+            _ => name
+                .strip_prefix("m=")
+                .and_then(|m| m.parse::<f64>().ok())
+                .filter(|m| *m >= 0.)
+                .map(Smoothing::MEstimate)
+                .ok_or_else(|| format!("unknown smoothing {}", name)),
+        }
+    }
+}
+
+impl Smoothing {
+    fn probabilities(self, distribution: &[f64], prior: &[f64]) -> Vec<f64> {
+        let total_size = distribution.iter().sum::<f64>();
+        let prior_size = prior.iter().sum::<f64>();
+        let class_count = distribution.len() as f64;
+        distribution
+            .iter()
+            .zip(prior)
+            .map(|(count, prior_count)| {
+                match self {
+                    Smoothing::None if total_size == 0. => 1. / class_count,
+                    Smoothing::None => count / total_size,
+                    Smoothing::Laplace => (count + 1.) / (total_size + class_count),
+                    Smoothing::MEstimate(m) if total_size + m == 0. => 1. / class_count,
+                    Smoothing::MEstimate(m) => {
+                        (count + m * (prior_count / prior_size)) / (total_size + m)
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// What prediction does at a branch with no path for the record's value,
+/// because it is `?` or was never seen in training.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum MissingValues {
+    /// Try the branch's surrogate splits, then stop at the branch and use
+    /// its majority class
+    #[default]
+    Majority,
+    /// Follow every path and combine their class distributions, weighted by
+    /// the training weight that took each one (C4.5). Branches keep no
+    /// surrogate splits.
+    Distribute,
+}
+
+impl FromStr for MissingValues {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "majority" => Ok(MissingValues::Majority),
+            "distribute" => Ok(MissingValues::Distribute),
+            _ => Err(format!("unknown missing value mode {}", name)),
+        }
+    }
+}
+
+/// How a node picks its class when several classes share the majority.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum TieBreak {
+    /// The parent's majority class if it is among the tied ones
+    #[default]
+    ParentMajority,
+    /// The tied class that is most frequent in the whole training set
+    GlobalPrior,
+    LowestIndex,
+}
+
+impl FromStr for TieBreak {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "parent" => Ok(TieBreak::ParentMajority),
+            "prior" => Ok(TieBreak::GlobalPrior),
+            "lowest" => Ok(TieBreak::LowestIndex),
+            _ => Err(format!("unknown tie break {}", name)),
+        }
+    }
+}
+
+impl TieBreak {
+    /// Every policy falls back to the lowest tied index.
+    fn majority_class(self, distribution: &[f64], parent_class: Option<usize>, prior: &[f64]) -> usize {
+        let max = distribution.iter().cloned().fold(0., f64::max);
+        let tied: Vec<usize> = distribution.iter().positions(|count| *count == max).collect();
+        let preferred = match self {
+            TieBreak::ParentMajority => parent_class.filter(|class| tied.contains(class)),
+            TieBreak::GlobalPrior => tied
+                .iter()
+                .cloned()
+                .min_by(|x, y| prior[*y].total_cmp(&prior[*x])),
+            TieBreak::LowestIndex => None,
+        };
+        preferred.unwrap_or(tied[0])
+    }
+}
+
+/// Extra training weight given to every record of a class.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum ClassWeights {
+    #[default]
+    None,
+    /// Inverse class frequency, so every class carries the same total weight
+    Balanced,
+    /// One weight per value of the target attribute, in declaration order
+    Explicit(Vec<f64>),
+}
+
+impl FromStr for ClassWeights {
+    type Err = String;
+
+    /// `none`, `balanced` or a comma-separated list such as `1,4.5,2`
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "none" => Ok(ClassWeights::None),
+            "balanced" => Ok(ClassWeights::Balanced),
+            _ => name
+                .split(',')
+                .map(|weight| weight.trim().parse::<f64>().ok().filter(|weight| *weight >= 0.))
+                .collect::<Option<Vec<f64>>>()
+                .map(ClassWeights::Explicit)
+                .ok_or_else(|| format!("unknown class weights {}", name)),
+        }
+    }
+}
+
+impl ClassWeights {
+    /// Weight of every class given the unweighted class totals `prior`.
+    fn weights(&self, prior: &[f64]) -> Vec<f64> {
+        match self {
+            ClassWeights::None => vec![1.; prior.len()],
+            ClassWeights::Balanced => {
+                let total: f64 = prior.iter().sum();
+                let class_count = prior.iter().filter(|count| **count > 0.).count() as f64;
+                prior
+                    .iter()
+                    .map(|count| if *count > 0. { total / (class_count * count) } else { 0. })
+                    .collect()
+            }
+            ClassWeights::Explicit(weights) => {
+                assert_eq!(weights.len(), prior.len(), "one class weight per class value");
+                weights.clone()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct TrainingStats {
+    /// Impure leaves made because no feature was left to split on
+    pub undecided_leaves: usize,
+}
+
+/// Hyperparameters of a `DecisionTree`, set builder style, e.g.
+/// `TreeConfig::default().max_depth(4).min_samples_leaf(2)`.
+#[derive(Debug, Clone)]
+pub struct TreeConfig {
+    measure: SplitMeasure,
+    criterion: Criterion,
+    tie_break: TieBreak,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) min_samples_split: usize,
+    pub(crate) min_samples_leaf: usize,
+    pub(crate) min_information_gain: f64,
+    pub(crate) max_leaf_nodes: Option<usize>,
+    smoothing: Smoothing,
+    missing_values: MissingValues,
+    surrogates: usize,
+    cost_matrix: Option<CostMatrix>,
+    reweight: bool,
+    class_weights: ClassWeights,
+    pub(crate) regression_criterion: RegressionCriterion,
+    pub(crate) leaf_prediction: LeafPrediction,
+}
+
+impl Default for TreeConfig {
+    fn default() -> Self {
+        Self {
+            measure: SplitMeasure::default(),
+            criterion: Criterion::default(),
+            tie_break: TieBreak::default(),
+            max_depth: None,
+            min_samples_split: 2,
+            min_samples_leaf: 1,
+            min_information_gain: 0.,
+            max_leaf_nodes: None,
+            smoothing: Smoothing::default(),
+            missing_values: MissingValues::default(),
+            surrogates: 3,
+            cost_matrix: None,
+            reweight: false,
+            class_weights: ClassWeights::default(),
+            regression_criterion: RegressionCriterion::default(),
+            leaf_prediction: LeafPrediction::default(),
+        }
+    }
+}
+
+impl TreeConfig {
+    pub fn measure(mut self, measure: SplitMeasure) -> Self {
+        self.measure = measure;
+        self
+    }
+
+    pub fn criterion(mut self, criterion: Criterion) -> Self {
+        self.criterion = criterion;
+        self
+    }
+
+    pub fn tie_break(mut self, tie_break: TieBreak) -> Self {
+        self.tie_break = tie_break;
+        self
+    }
+
+    /// Number of splits allowed between the root and any leaf.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Nodes with fewer records become leaves.
+    pub fn min_samples_split(mut self, min_samples_split: usize) -> Self {
+        self.min_samples_split = min_samples_split;
+        self
+    }
+
+    /// Splits leaving a child with fewer records are not considered.
+    pub fn min_samples_leaf(mut self, min_samples_leaf: usize) -> Self {
+        self.min_samples_leaf = min_samples_leaf;
+        self
+    }
+
+    /// Splits gaining less, by the configured criterion, are not made.
+    pub fn min_information_gain(mut self, min_information_gain: f64) -> Self {
+        self.min_information_gain = min_information_gain;
+        self
+    }
+
+    pub fn max_leaf_nodes(mut self, max_leaf_nodes: usize) -> Self {
+        self.max_leaf_nodes = Some(max_leaf_nodes);
+        self
+    }
+
+    pub fn smoothing(mut self, smoothing: Smoothing) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    pub fn missing_values(mut self, missing_values: MissingValues) -> Self {
+        self.missing_values = missing_values;
+        self
+    }
+
+    /// Surrogate splits kept per branch for records missing its feature.
+    /// `MissingValues::Distribute` takes precedence and keeps none.
+    pub fn surrogates(mut self, surrogates: usize) -> Self {
+        self.surrogates = surrogates;
+        self
+    }
+
+    /// Leaves predict the class with the lowest expected cost instead of the
+    /// majority class.
+    pub fn cost_matrix(mut self, cost_matrix: CostMatrix) -> Self {
+        self.cost_matrix = Some(cost_matrix);
+        self
+    }
+
+    /// Weight the training records of every class by how costly it is to
+    /// misclassify instead, so splits favour separating the expensive classes
+    /// and the reweighted majority already is the cheapest class.
+    pub fn reweight(mut self, reweight: bool) -> Self {
+        self.reweight = reweight;
+        self
+    }
+
+    /// Scales the training weight of every record by its class' weight, which
+    /// carries over into the gain of every split and the class of every leaf.
+    pub fn class_weights(mut self, class_weights: ClassWeights) -> Self {
+        self.class_weights = class_weights;
+        self
+    }
+
+    /// Whether splitting a node into `children` keeps the tree within
+    /// `max_leaf_nodes`, with `leaf_count` leaves made and `pending` nodes
+    /// left on the stack. Every pending node ends up as at least one leaf.
+    pub(crate) fn leaf_budget_allows(&self, leaf_count: usize, pending: usize, children: usize) -> bool {
+        self.max_leaf_nodes
+            .is_none_or(|max_leaf_nodes| leaf_count + pending + children <= max_leaf_nodes)
+    }
+
+    /// Class a node with `distribution` predicts: the majority class, or the
+    /// cheapest one when there is a cost matrix the training data wasn't
+    /// reweighted by. Ties go to the tie break.
+    fn label(&self, distribution: &[f64], parent_class: Option<usize>, prior: &[f64]) -> usize {
+        match self.cost_matrix.as_ref().filter(|_| !self.reweight) {
+            Some(cost_matrix) => {
+                let costs = cost_matrix.expected_costs(distribution);
+                let worst = costs.iter().cloned().fold(0., f64::max);
+                let savings: Vec<f64> = costs.iter().map(|cost| worst - cost).collect();
+                self.tie_break.majority_class(&savings, parent_class, prior)
+            }
+            None => self.tie_break.majority_class(distribution, parent_class, prior),
+        }
+    }
+
+    /// Impurity used by `RegressionTree`.
+    pub fn regression_criterion(mut self, regression_criterion: RegressionCriterion) -> Self {
+        self.regression_criterion = regression_criterion;
+        self
+    }
+
+    /// What a `RegressionTree` leaf predicts.
+    pub fn leaf_prediction(mut self, leaf_prediction: LeafPrediction) -> Self {
+        self.leaf_prediction = leaf_prediction;
+        self
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct DecisionTree {
     nodes: Vec<Node>,
+    config: TreeConfig,
+    stats: TrainingStats,
+    /// Class weights of the whole training set
+    prior: Vec<f64>,
+    /// Attribute index of the class
+    target: usize,
 }
 
-fn calculate_information(distribution: &[u32], total_size: f32) -> f32 {
+fn build_distribution(sub_set: &[(&Record, f64)], total_size: usize) -> Vec<f64> {
+    let mut distribution = vec![0.; total_size];
+    for (record, weight) in sub_set.iter() {
+        distribution[record.class_index()] += weight;
+    }
     distribution
+}
+
+/// How `train` ranks the candidate splits of a node.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum SplitMeasure {
+    #[default]
+    InformationGain,
+    /// Gain divided by split information, only among the candidates with at
+    /// least average gain (C4.5).
+    GainRatio,
+}
+
+/// A candidate split of the records at a node, with the class distribution of
+/// every child among the records whose value is known.
+struct Split {
+    feature: usize,
+    threshold: Option<f64>,
+    distributions: Vec<Vec<f64>>,
+    /// Weight of the records missing the feature's value
+    missing: f64,
+}
+
+impl Split {
+    /// Gain over the records with a known value, scaled by their share of the
+    /// node (C4.5).
+    fn gain(&self, criterion: Criterion, total_size: f64) -> f64 {
+        let known = self.distributions.iter().fold(Vec::new(), |mut acc: Vec<f64>, distribution| {
+            acc.resize(distribution.len(), 0.);
+            acc.iter_mut().zip(distribution).for_each(|(a, d)| *a += d);
+            acc
+        });
+        let known_size = total_size - self.missing;
+        if known_size <= 0. {
+            return 0.;
+        }
+        (known_size / total_size) * criterion.gain(&known, &self.distributions)
+    }
+
+    /// Records missing the value count as one more child.
+    fn split_information(&self, total_size: f64) -> f64 {
+        let mut child_sizes: Vec<f64> = self
+            .distributions
+            .iter()
+            .map(|distribution| distribution.iter().sum())
+            .collect();
+        child_sizes.push(self.missing);
+        split_information(&child_sizes, total_size)
+    }
+}
+
+/// Best `value <= threshold` split of a continuous feature, C4.5 style. Only
+/// midpoints between adjacent distinct values whose classes differ are tried.
+fn best_threshold(
+    criterion: Criterion,
+    min_samples_leaf: usize,
+    sub_set: &[(&Record, f64)],
+    feature: usize,
+    output_count: usize,
+) -> Option<Split> {
+    let mut values: Vec<(f64, usize, f64)> = sub_set
         .iter()
-        .filter(|val| **val > 0)
-        .map(|val| *val as f32)
-        .map(|val| val / total_size)
-        .map(|frac| frac * frac.log2())
-        .fold(0., |acc, v| acc + v)
+        .filter_map(|(record, weight)| match record.features[feature] {
+            Value::Continuous(value) => Some((value, record.class_index(), *weight)),
+            _ => None,
+        })
+        .collect();
+    values.sort_by(|x, y| x.0.total_cmp(&y.0));
+    // One class distribution per distinct value
+    let mut groups: Vec<(f64, Vec<f64>)> = Vec::new();
+    for (value, class, weight) in values {
+        match groups.last_mut() {
+            Some((last, distribution)) if *last == value => distribution[class] += weight,
+            _ => {
+                let mut distribution = vec![0.; output_count];
+                distribution[class] += weight;
+                groups.push((value, distribution));
+            }
+        }
+    }
+    let single_class = |distribution: &Vec<f64>| {
+        let mut classes = distribution.iter().positions(|count| *count > 0.);
+        match (classes.next(), classes.next()) {
+            (Some(class), None) => Some(class),
+            _ => None,
+        }
+    };
+    let mut below = vec![0.; output_count];
+    let mut above = groups.iter().fold(vec![0.; output_count], |mut acc, (_, distribution)| {
+        acc.iter_mut().zip(distribution).for_each(|(a, d)| *a += d);
+        acc
+    });
+    let known_size: f64 = above.iter().sum();
+    let missing = sub_set.iter().map(|(_, weight)| weight).sum::<f64>() - known_size;
+    let mut best: Option<(f64, Split)> = None;
+    for pair in groups.windows(2) {
+        let (value, distribution) = &pair[0];
+        let (next_value, next_distribution) = &pair[1];
+        below.iter_mut().zip(distribution).for_each(|(b, d)| *b += d);
+        above.iter_mut().zip(distribution).for_each(|(a, d)| *a -= d);
+        let boundary = match (single_class(distribution), single_class(next_distribution)) {
+            (Some(class), Some(next_class)) => class != next_class,
+            _ => true,
+        };
+        let big_enough = [&below, &above]
+            .iter()
+            .all(|side| side.iter().sum::<f64>() >= min_samples_leaf as f64);
+        if boundary && big_enough {
+            let distributions = vec![below.clone(), above.clone()];
+            let score = criterion.remaining(&distributions, known_size);
+            if best.as_ref().is_none_or(|(best_score, _)| score < *best_score) {
+                let split = Split {
+                    feature,
+                    threshold: Some((value + next_value) / 2.),
+                    distributions,
+                    missing,
+                };
+                best = Some((score, split));
+            }
+        }
+    }
+    best.map(|(_, split)| split)
+}
+
+/// Picks the best of `candidates` by `measure`, the first one on ties.
+fn choose_split(
+    measure: SplitMeasure,
+    criterion: Criterion,
+    parent: &[f64],
+    candidates: Vec<Split>,
+) -> Option<Split> {
+    let total_size = parent.iter().sum::<f64>();
+    let mut scored: Vec<(f64, Split)> = match measure {
+        SplitMeasure::InformationGain => candidates
+            .into_iter()
+            .map(|split| (split.gain(criterion, total_size), split))
+            .collect(),
+        SplitMeasure::GainRatio => {
+            // A split that sends everything down one path has no ratio
+            let gains: Vec<(f64, f64, Split)> = candidates
+                .into_iter()
+                .map(|split| {
+                    let gain = split.gain(criterion, total_size);
+                    (gain, split.split_information(total_size), split)
+                })
+                .filter(|(_, split_info, _)| *split_info > 0.)
+                .collect();
+            let average_gain = gains.iter().map(|(gain, _, _)| gain).sum::<f64>() / gains.len() as f64;
+            gains
+                .into_iter()
+                .filter(|(gain, _, _)| *gain >= average_gain - 1e-6)
+                .map(|(gain, split_info, split)| (gain / split_info, split))
+                .collect()
+        }
+    };
+    let best = scored
+        .iter()
+        .enumerate()
+        .min_by(|x, y| (y.1).0.total_cmp(&(x.1).0))
+        .map(|(index, _)| index)?;
+    Some(scored.swap_remove(best).1)
 }
 
-fn build_distribution(sub_set: &[&Record], total_size: usize) -> Vec<u32> {
-    let mut distribution = Vec::with_capacity(total_size);
-    for _ in 0..total_size {
-        distribution.push(0);
+/// Used features, the records that reached the node with the fraction of each
+/// that did, the parent branch and path key that lead to it, and the node's
+/// depth.
+pub(crate) type StackEntry<'a> = (Vec<usize>, Vec<(&'a Record, f64)>, Option<(usize, usize)>, usize);
+
+/// Nodes left to grow while training a tree, depth first from the root.
+pub(crate) struct Frontier<'a> {
+    root: Option<Vec<(&'a Record, f64)>>,
+    stack: Vec<StackEntry<'a>>,
+}
+
+impl<'a> Frontier<'a> {
+    pub(crate) fn new(training: Vec<(&'a Record, f64)>) -> Self {
+        Self {
+            root: Some(training),
+            stack: Vec::new(),
+        }
     }
-    for record in sub_set.iter() {
-        distribution[record.class] += 1;
+
+    pub(crate) fn pop(&mut self) -> Option<StackEntry<'a>> {
+        match self.root.take() {
+            Some(training) => Some((Vec::new(), training, None, 0)),
+            None => self.stack.pop(),
+        }
     }
-    distribution
+
+    /// Number of nodes still to grow besides the one just popped
+    pub(crate) fn pending(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Queues a child for every path of the branch at index `parent` that
+    /// records took.
+    pub(crate) fn push_children(
+        &mut self,
+        used_features: &[usize],
+        children: HashMap<usize, Vec<(&'a Record, f64)>>,
+        parent: usize,
+        depth: usize,
+    ) {
+        for (path, child) in children {
+            if !child.is_empty() {
+                self.stack.push((used_features.to_vec(), child, Some((parent, path)), depth + 1));
+            }
+        }
+    }
+}
+
+/// Sends the records `missing` the split value down every path of
+/// `children`, weighted by the share of the known weight that took it, or
+/// evenly when the known records weigh nothing.
+pub(crate) fn distribute_missing<'a>(
+    children: &mut HashMap<usize, Vec<(&'a Record, f64)>>,
+    missing: Vec<(&'a Record, f64)>,
+) {
+    let child_sizes: Vec<(usize, f64)> = children
+        .iter()
+        .map(|(path, child)| (*path, child.iter().map(|(_, weight)| weight).sum()))
+        .collect();
+    let known_size: f64 = child_sizes.iter().map(|(_, child_size)| child_size).sum();
+    for (record, weight) in missing {
+        for (path, child_size) in child_sizes.iter() {
+            let share = if known_size > 0. {
+                child_size / known_size
+            } else {
+                1. / child_sizes.len() as f64
+            };
+            children.get_mut(path).unwrap().push((record, weight * share));
+        }
+    }
+}
+
+/// A node of a tree grown from a `Frontier`.
+pub(crate) trait TreeNode {
+    /// Child node indices by path key, `None` for a leaf
+    fn paths_mut(&mut self) -> Option<&mut HashMap<usize, usize>>;
+}
+
+/// Points the path in `parent_info` at the node about to be pushed onto
+/// `nodes` and returns the branch it leaves from.
+pub(crate) fn attach<N: TreeNode>(nodes: &mut [N], parent_info: Option<(usize, usize)>) -> Option<&N> {
+    let (index, path) = parent_info?;
+    let node_index = nodes.len();
+    nodes[index].paths_mut()?.insert(path, node_index);
+    Some(&nodes[index])
+}
+
+/// Attributes but the `target` a node may split on. Continuous features can
+/// be split again at a different threshold, nominal ones only once per path.
+pub(crate) fn candidate_features<'a>(
+    class_tags: &'a [Class],
+    target: usize,
+    used_features: &'a [usize],
+) -> impl Iterator<Item = (usize, &'a Class)> + 'a {
+    class_tags.iter().enumerate().filter(move |(feature, class_tag)| {
+        *feature != target && !(matches!(class_tag, Class::Nominal(_)) && used_features.contains(feature))
+    })
 }
 
 use crate::arff::Class;
 impl DecisionTree {
-    pub fn train(&mut self, data: &[Record], class_tags: &Vec<Class>) {
+    pub fn new(config: TreeConfig) -> Self {
+        Self {
+            nodes: Vec::new(),
+            config,
+            stats: TrainingStats::default(),
+            prior: Vec::new(),
+            target: 0,
+        }
+    }
+
+    pub fn training_stats(&self) -> &TrainingStats {
+        &self.stats
+    }
+
+    /// Learns to predict attribute `target` of `data`, described by
+    /// `class_tags`, from the other attributes.
+    pub fn train(&mut self, data: &[Record], class_tags: &[Class], target: usize) {
         // Forget previous training
         self.nodes = Vec::new();
-        let mut stack: Vec<(Vec<usize>, Vec<&Record>, Option<(usize, usize)>)> =
-            Vec::with_capacity((2.0_f32).powf(class_tags.len() as f32) as usize);
-        let training_count = (data.len() as f32 * 1.) as usize;
-        let (training, validation) = data.split_at(training_count);
-        let mut previous_accuracy = 0.;
-        loop {
-            let (mut used_features, sub_set, parent_info) = stack
-                .pop()
-                .unwrap_or_else(|| (Vec::new(), training.iter().collect(), None));
-            let output_count = if let Some(Class::Nominal(class)) = class_tags.last() {
-                class.len()
+        self.stats = TrainingStats::default();
+        self.target = target;
+        let output_count = if let Class::Nominal(class) = &class_tags[target] {
+            class.len()
+        } else {
+            unreachable!();
+        };
+        // Records without a class can't be learned from
+        let mut training: Vec<(&Record, f64)> = data
+            .iter()
+            .filter(|record| record.class_known())
+            .map(|record| (record, record.weight))
+            .collect();
+        let config = self.config.clone();
+        let mut class_weights = config.class_weights.weights(&build_distribution(&training, output_count));
+        if let Some(cost_matrix) = config.cost_matrix.as_ref().filter(|_| config.reweight) {
+            let cost_weights = cost_matrix.class_weights(&build_distribution(&training, output_count));
+            class_weights.iter_mut().zip(cost_weights).for_each(|(weight, cost_weight)| *weight *= cost_weight);
+        }
+        for (record, weight) in training.iter_mut() {
+            *weight *= class_weights[record.class_index()];
+        }
+        let prior = build_distribution(&training, output_count);
+        // Distributing records over every path replaces surrogate splits
+        let surrogate_count = match config.missing_values {
+            MissingValues::Majority => config.surrogates,
+            MissingValues::Distribute => 0,
+        };
+        let mut frontier = Frontier::new(training);
+        let mut leaf_count = 0;
+        while let Some((mut used_features, sub_set, parent_info, depth)) = frontier.pop() {
+            let distribution = build_distribution(&sub_set, output_count);
+            let size: f64 = distribution.iter().sum();
+            let class_count = distribution.iter().filter(|count| **count > 0.).count();
+            let can_grow = size >= config.min_samples_split as f64
+                && config.max_depth.is_none_or(|max_depth| depth < max_depth);
+            let best_split = if class_count == 1 || !can_grow {
+                None
             } else {
-                unreachable!();
+                let candidates = candidate_features(class_tags, target, &used_features)
+                    .filter_map(|(feature, class_tag)| match class_tag {
+                        Class::Nominal(_) => {
+                            let mut distributions: HashMap<usize, Vec<f64>> = HashMap::new();
+                            let mut missing = 0.;
+                            for (record, weight) in sub_set.iter() {
+                                match path_key(None, record.features[feature]) {
+                                    Some(path) => {
+                                        distributions
+                                            .entry(path)
+                                            .or_insert_with(|| vec![0.; output_count])
+                                            [record.class_index()] += weight
+                                    }
+                                    None => missing += weight,
+                                }
+                            }
+                            let distributions: Vec<Vec<f64>> = distributions.into_values().collect();
+                            let big_enough = distributions.iter().all(|distribution| {
+                                distribution.iter().sum::<f64>() >= config.min_samples_leaf as f64
+                            });
+                            if big_enough && !distributions.is_empty() {
+                                Some(Split {
+                                    feature,
+                                    threshold: None,
+                                    distributions,
+                                    missing,
+                                })
+                            } else {
+                                None
+                            }
+                        }
+                        Class::String(_) => None,
+                        Class::Continuous | Class::Date(_) => best_threshold(
+                            config.criterion,
+                            config.min_samples_leaf,
+                            &sub_set,
+                            feature,
+                            output_count,
+                        ),
+                    })
+                    .collect();
+                choose_split(config.measure, config.criterion, &distribution, candidates)
             };
-            let distribution = build_distribution(&sub_set, output_count);
-            let class_count = distribution.iter().filter(|count| **count > 0).count();
-            // avoid making dead connections
-            if class_count == 1 || used_features.len() < class_tags.len() - 1 {
-                if let Some((index, feature)) = parent_info {
-                    let node_index = self.nodes.len();
-                    if let Node::Branch(ref mut parent) = &mut self.nodes[index] {
-                        parent.paths.insert(feature, node_index);
-                    }
-                }
-            }
+            let undecided = class_count > 1 && can_grow && best_split.is_none();
+            let best_split = best_split
+                .filter(|split| split.gain(config.criterion, size) >= config.min_information_gain)
+                .filter(|split| config.leaf_budget_allows(leaf_count, frontier.pending(), split.distributions.len()));
+            let parent_class = match attach(&mut self.nodes, parent_info) {
+                Some(Node::Branch(parent)) => Some(parent.majority_class),
+                _ => None,
+            };
+            let majority_class = config.label(&distribution, parent_class, &prior);
             if class_count == 1 {
+                leaf_count += 1;
                 self.nodes.push(Node::Leaf(LeafNode {
-                    class: sub_set[0].class,
+                    class: majority_class,
+                    distribution,
                 }));
-            } else if used_features.len() < class_tags.len() - 1 {
-                let (feature, _info) = class_tags
-                    .split_last()
-                    .unwrap()
-                    .1
-                    .iter()
-                    .enumerate()
-                    .filter(|(index, _)| {
-                        used_features
-                            .iter()
-                            .all(|feature_index| feature_index != index)
-                    })
-                    .map(|(feature, classes)| {
-                        let count = match classes {
-                            Class::Nominal(list) => list.len(),
-                            Class::Continuous(max) => *max,
-                        };
-                        (
-                            feature,
-                            (0..count)
-                                .into_iter()
-                                .map(|feature_value| {
-                                    let potential_sub_set: Vec<&Record> = sub_set
-                                        .iter()
-                                        .cloned()
-                                        .filter(|record| record.features[feature] == feature_value)
-                                        .collect();
-                                    let distribution = build_distribution(
-                                        potential_sub_set.as_slice(),
-                                        output_count,
-                                    );
-                                    calculate_information(&distribution, sub_set.len() as f32)
-                                        * (potential_sub_set.len() as f32 / sub_set.len() as f32)
-                                        * -1.
-                                })
-                                .fold(0., |acc, v| acc + v),
-                        )
-                    })
-                    .min_by(|x, y| x.1.partial_cmp(&y.1).unwrap())
-                    .unwrap();
-                used_features.push(feature);
-                let mut child_sub_sets = sub_set
-                    .iter()
-                    .map(|record| (record.features[feature], *record))
-                    .into_group_map();
-                let (majority_class, _) = distribution.iter().enumerate().max_by(|x, y| x.1.cmp(y.1)).unwrap();
-                self.nodes.push(Node::Branch(BranchNode {
+            } else if let Some(Split { feature, threshold, .. }) = best_split {
+                if threshold.is_none() {
+                    used_features.push(feature);
+                }
+                let branch = BranchNode {
                     feature,
                     paths: HashMap::new(),
+                    threshold,
                     majority_class,
-                }));
-                for (feature, child_subset) in child_sub_sets.drain() {
-                    if !child_subset.is_empty() {
-                        stack.push((
-                            used_features.clone(),
-                            child_subset,
-                            Some((self.nodes.len() - 1, feature)),
-                        ));
+                    distribution,
+                    surrogates: find_surrogates(&sub_set, feature, threshold, class_tags, target, surrogate_count),
+                };
+                let mut child_sub_sets: HashMap<usize, Vec<(&Record, f64)>> = HashMap::new();
+                let mut missing = Vec::new();
+                for (record, weight) in sub_set {
+                    match branch.path_key(record.features[feature]) {
+                        Some(path) => child_sub_sets.entry(path).or_default().push((record, weight)),
+                        None => missing.push((record, weight)),
                     }
                 }
+                distribute_missing(&mut child_sub_sets, missing);
+                self.nodes.push(Node::Branch(branch));
+                frontier.push_children(&used_features, child_sub_sets, self.nodes.len() - 1, depth);
             } else {
-                println!("undecided: used {:?}:  {:?}", used_features, distribution);
-            }
-            let test_accuracy = self.test_set(training);
-            // dbg!(test_accuracy);
-            let validation_accuracy = self.test_set(validation);
-            let _stagnant = validation_accuracy - previous_accuracy < 0.001;
-            if stack.is_empty() {
-                break;
+                if undecided {
+                    self.stats.undecided_leaves += 1;
+                }
+                leaf_count += 1;
+                self.nodes.push(Node::Leaf(LeafNode {
+                    class: majority_class,
+                    distribution,
+                }));
             }
-            previous_accuracy = validation_accuracy;
         }
+        self.prior = prior;
         // dbg!(&self.nodes);
     }
+    /// Weighted accuracy on `records`.
     fn test_set(&self, records: &[Record]) -> f32 {
-        records.iter().map(|record| {
-            if self.predict(record) == record.class { 1 } else { 0 }
-        }).fold(0, |acc, x| acc + x) as f32 / records.len() as f32
+        let known = records.iter().filter(|record| record.class_known());
+        let correct: f64 = known
+            .clone()
+            .filter(|record| self.predict(record) == record.class_index())
+            .map(|record| record.weight)
+            .sum();
+        (correct / known.map(|record| record.weight).sum::<f64>()) as f32
     }
 
-    pub fn predict(&self, record: &Record) -> usize {
-        let mut node_index = 0;
+    /// Index of the node `record` ends up at: a leaf, or a branch without a
+    /// path for its value.
+    fn find_node(&self, record: &Record) -> usize {
+        self.find_node_from(0, record)
+    }
+
+    fn find_node_from(&self, mut node_index: usize, record: &Record) -> usize {
         loop {
             let node = &self.nodes[node_index];
             if let Node::Branch(ref branch) = node {
-                if let Some(index) = branch.paths.get(&record.features[branch.feature]) {
-                    node_index = *index;
+                if let Some(index) = branch.follow(record) {
+                    node_index = index;
                 } else {
                     // println!("falling back to majority on Record: {:?}", record);
-                    return branch.majority_class;
+                    return node_index;
                 }
             }
-            if let Node::Leaf(ref leaf) = node {
-                return leaf.class;
+            if let Node::Leaf(_) = node {
+                return node_index;
             }
         }
     }
 
-    pub fn print_tree(&self, depth: usize, class_tags: &Vec<Class>, labels: &Vec<String>) {
-        let mut current_depth = 0;
-        let mut stack: Vec<((usize, usize), usize)> = vec![((0, 0), 0)]; 
-        loop {
-            let mut next_stack = Vec::new();
-            for ((previous_choice, previous_feature), node_index) in stack.drain(..) {
-                let node = &self.nodes[node_index];
-                let previous_value = match &class_tags[previous_feature] {
-                    Class::Continuous(_) => format!("{}", previous_choice),
-                    Class::Nominal(list) => list[previous_choice].clone(),
-                };
-                match node {
-                    Node::Branch(branch) => {
-                        print!("b({}: {}, {}) | ", labels[previous_feature], previous_value, labels[branch.feature]);
-                        for (k, v) in branch.paths.iter() {
-                            next_stack.push(((*k, branch.feature), *v));
-                        }
-                    },
-                    Node::Leaf(leaf) => {
-                        let class = match &class_tags[class_tags.len() - 1] {
-                            Class::Nominal(list) => list[leaf.class].clone(),
-                            Class::Continuous(_) => format!("{}", leaf.class),
-                        };
-                        print!("l({}: {}, {}) | ", labels[previous_feature], previous_value, class);
-                    },
-                }
+    pub fn predict(&self, record: &Record) -> usize {
+        match &self.nodes[self.find_node(record)] {
+            Node::Branch(_) if self.config.missing_values == MissingValues::Distribute => {
+                let probabilities = self.predict_proba(record);
+                self.config.label(&probabilities, None, &self.prior)
             }
-            println!("");
-            current_depth += 1;
-            if next_stack.is_empty() || current_depth >= depth {
-                break;
+            Node::Branch(branch) => branch.majority_class,
+            Node::Leaf(leaf) => leaf.class,
+        }
+    }
+
+    /// Class probabilities from the training counts of the node `record` ends
+    /// up at, smoothed as configured. With `MissingValues::Distribute` a
+    /// branch without a path for the value combines all of its children.
+    pub fn predict_proba(&self, record: &Record) -> Vec<f64> {
+        self.node_proba(self.find_node(record), record)
+    }
+
+    fn node_proba(&self, node_index: usize, record: &Record) -> Vec<f64> {
+        let node = &self.nodes[node_index];
+        match node {
+            Node::Branch(branch)
+                if self.config.missing_values == MissingValues::Distribute && !branch.paths.is_empty() =>
+            {
+                let child_sizes: Vec<(usize, f64)> = branch
+                    .paths
+                    .values()
+                    .map(|child| (*child, self.nodes[*child].distribution().iter().sum()))
+                    .collect();
+                let known_size: f64 = child_sizes.iter().map(|(_, child_size)| child_size).sum();
+                child_sizes.iter().fold(
+                    vec![0.; branch.distribution.len()],
+                    |mut acc, (child, child_size)| {
+                        let child_node = self.find_node_from(*child, record);
+                        let probabilities = self.node_proba(child_node, record);
+                        acc.iter_mut()
+                            .zip(probabilities)
+                            .for_each(|(a, p)| *a += p * child_size / known_size);
+                        acc
+                    },
+                )
             }
-            stack = next_stack;
-        };
+            _ => self.config.smoothing.probabilities(node.distribution(), &self.prior),
+        }
+    }
+
+    pub fn print_tree(&self, depth: usize, class_tags: &[Class], labels: &[String]) {
+        print_levels(depth, class_tags, labels, |node_index| match &self.nodes[node_index] {
+            Node::Branch(branch) => PrintedNode::Branch {
+                feature: branch.feature,
+                threshold: branch.threshold,
+                paths: &branch.paths,
+            },
+            Node::Leaf(leaf) => PrintedNode::Leaf(match &class_tags[self.target] {
+                Class::Nominal(list) => list[leaf.class].clone(),
+                _ => format!("{}", leaf.class),
+            }),
+        });
     }
 
     pub fn prune(&mut self, validation_set: &[Record]) {
@@ -221,7 +1023,7 @@ impl DecisionTree {
             let current_accuracy = self.test_set(validation_set);
             let worst_node = (0..self.nodes.len()).map(|skipped_node| {
                 let (accuracy, saved_paths) = if let Node::Branch(ref mut branch) = self.nodes[skipped_node] {
-                    let saved_paths = std::mem::replace(&mut branch.paths, HashMap::new());
+                    let saved_paths = std::mem::take(&mut branch.paths);
                     if saved_paths.is_empty() {
                         (0., None)
                     } else {
@@ -236,7 +1038,7 @@ impl DecisionTree {
                     }
                 }
                 accuracy
-            }).enumerate().max_by(|x, y| x.1.partial_cmp(&y.1).unwrap()).unwrap();
+            }).enumerate().max_by(|x, y| x.1.total_cmp(&y.1)).unwrap();
             // dbg!((worst_node, current_accuracy));
             if worst_node.1 >= current_accuracy {
                 if let Node::Branch(ref mut branch) = self.nodes[worst_node.0] {
@@ -256,7 +1058,7 @@ impl DecisionTree {
             Node::Branch(branch) => {
                 branch.paths.values().map(|node| {
                     1 + self.count_children(*node)
-                }).fold(0, |acc, val| acc + val)
+                }).sum()
             },
             Node::Leaf(_) => {
                 1
@@ -285,6 +1087,7 @@ impl DecisionTree {
         self.max_children(0)
     }
 
+    #[allow(dead_code)]
     pub fn count_pruned_nodes(&self) -> usize {
         self.nodes.len() - self.count_children(0)
     }
@@ -0,0 +1,203 @@
+use crate::arff::Class;
+use crate::criterion::Criterion;
+use crate::record::{Record, Value};
+use std::str::FromStr;
+
+/// How `Discretizer::fit` places the cut points of an attribute.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Binning {
+    /// Intervals of the same width between the smallest and largest value
+    EqualWidth(usize),
+    /// Intervals holding about the same weight of records
+    EqualFrequency(usize),
+    /// Cut points chosen by class entropy, kept while they pass the minimum
+    /// description length criterion (Fayyad and Irani, 1993)
+    Mdl,
+}
+
+impl FromStr for Binning {
+    type Err = String;
+
+    /// `mdl`, `width=<bins>` or `frequency=<bins>`
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let bins = |prefix: &str| {
+            name.strip_prefix(prefix)
+                .and_then(|bins| bins.parse::<usize>().ok())
+                .filter(|bins| *bins > 0)
+        };
+        match name {
+            "mdl" => Ok(Binning::Mdl),
+            _ => bins("width=")
+                .map(Binning::EqualWidth)
+                .or_else(|| bins("frequency=").map(Binning::EqualFrequency))
+                .ok_or_else(|| format!("unknown binning {}", name)),
+        }
+    }
+}
+
+/// Cut points fitted to a dataset, so data read later is binned the same
+/// way. A value falls in the first interval whose upper cut point it doesn't
+/// exceed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Discretizer {
+    pub binning: Binning,
+    /// Ascending cut points of every attribute, `None` for those left alone
+    pub cuts: Vec<Option<Vec<f64>>>,
+}
+
+impl Discretizer {
+    /// Fits `binning` to the known values of every continuous attribute but
+    /// the `target`, weighting records by their weight. MDL needs a nominal
+    /// target and only looks at records whose class is known.
+    pub fn fit(records: &[Record], classes: &[Class], target: usize, binning: Binning) -> Result<Self, String> {
+        let class_count = match &classes[target] {
+            Class::Nominal(values) => values.len(),
+            _ if binning == Binning::Mdl => return Err(String::from("MDL discretization needs a nominal class")),
+            _ => 0,
+        };
+        let cuts = classes
+            .iter()
+            .enumerate()
+            .map(|(index, class)| {
+                if index == target || !matches!(class, Class::Continuous) {
+                    return None;
+                }
+                let mut values: Vec<(f64, Value, f64)> = records
+                    .iter()
+                    .filter_map(|record| match record.features[index] {
+                        Value::Continuous(value) => Some((value, record.class, record.weight)),
+                        _ => None,
+                    })
+                    .collect();
+                values.sort_by(|x, y| x.0.total_cmp(&y.0));
+                Some(match binning {
+                    Binning::EqualWidth(bins) => equal_width(&values, bins),
+                    Binning::EqualFrequency(bins) => equal_frequency(&values, bins),
+                    Binning::Mdl => {
+                        let labeled: Vec<(f64, usize, f64)> = values
+                            .iter()
+                            .filter_map(|(value, class, weight)| match class {
+                                Value::Nominal(class) => Some((*value, *class, *weight)),
+                                _ => None,
+                            })
+                            .collect();
+                        let mut cuts = Vec::new();
+                        mdl_cuts(&labeled, class_count, &mut cuts);
+                        cuts
+                    }
+                })
+            })
+            .collect();
+        Ok(Self { binning, cuts })
+    }
+
+    /// Interval labels of every attribute that gets binned, such as
+    /// `(-inf-4.95]`, `(4.95-5.45]` and `(5.45-inf)`.
+    pub fn labels(&self, index: usize) -> Option<Vec<String>> {
+        let cuts = self.cuts.get(index)?.as_ref()?;
+        let bounds: Vec<String> = cuts.iter().map(|cut| format_cut(*cut)).collect();
+        let lower = Some("-inf").into_iter().chain(bounds.iter().map(String::as_str));
+        let upper = bounds.iter().map(String::as_str);
+        let mut labels: Vec<String> = lower
+            .clone()
+            .zip(upper)
+            .map(|(lower, upper)| format!("({}-{}]", lower, upper))
+            .collect();
+        labels.push(format!("({}-inf)", lower.last().unwrap()));
+        Some(labels)
+    }
+
+    /// `value` of attribute `index` replaced by the interval it falls in.
+    pub fn apply(&self, index: usize, value: Value) -> Value {
+        match (value, self.cuts.get(index)) {
+            (Value::Continuous(value), Some(Some(cuts))) => {
+                Value::Nominal(cuts.iter().take_while(|cut| value > **cut).count())
+            }
+            _ => value,
+        }
+    }
+}
+
+/// `cut` with at most six decimals and without trailing zeros.
+fn format_cut(cut: f64) -> String {
+    let text = format!("{:.6}", cut);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" => String::from("0"),
+        _ => text.to_owned(),
+    }
+}
+
+fn equal_width(values: &[(f64, Value, f64)], bins: usize) -> Vec<f64> {
+    let (min, max) = match (values.first(), values.last()) {
+        (Some(first), Some(last)) if first.0 < last.0 => (first.0, last.0),
+        _ => return Vec::new(),
+    };
+    let width = (max - min) / bins as f64;
+    (1..bins).map(|bin| min + width * bin as f64).collect()
+}
+
+/// Cuts halfway between the values where the running weight passes each
+/// multiple of a bin's share, never inside a run of equal values.
+fn equal_frequency(values: &[(f64, Value, f64)], bins: usize) -> Vec<f64> {
+    let total: f64 = values.iter().map(|(_, _, weight)| weight).sum();
+    let mut cuts = Vec::new();
+    let mut seen = 0.;
+    for pair in values.windows(2) {
+        seen += pair[0].2;
+        if pair[0].0 < pair[1].0 && seen >= total * (cuts.len() + 1) as f64 / bins as f64 {
+            cuts.push((pair[0].0 + pair[1].0) / 2.);
+            if cuts.len() + 1 == bins {
+                break;
+            }
+        }
+    }
+    cuts
+}
+
+fn distribution(values: &[(f64, usize, f64)], class_count: usize) -> Vec<f64> {
+    let mut distribution = vec![0.; class_count];
+    for (_, class, weight) in values {
+        distribution[*class] += weight;
+    }
+    distribution
+}
+
+/// Adds the cut points of the sorted `values` to `cuts` in ascending order,
+/// splitting at the boundary of least class entropy while the gain pays for
+/// the cut under the MDL principle.
+fn mdl_cuts(values: &[(f64, usize, f64)], class_count: usize, cuts: &mut Vec<f64>) {
+    let entropy = |distribution: &[f64]| Criterion::Entropy.impurity(distribution);
+    let present = |distribution: &[f64]| distribution.iter().filter(|weight| **weight > 0.).count() as f64;
+    let whole = distribution(values, class_count);
+    let total: f64 = whole.iter().sum();
+    let mut left = vec![0.; class_count];
+    let mut best: Option<(f64, usize, Vec<f64>)> = None;
+    for split in 1..values.len() {
+        let (_, class, weight) = values[split - 1];
+        left[class] += weight;
+        if values[split - 1].0 == values[split].0 {
+            continue;
+        }
+        let right: Vec<f64> = whole.iter().zip(left.iter()).map(|(all, left)| all - left).collect();
+        let left_total: f64 = left.iter().sum();
+        let remaining = (left_total * entropy(&left) + (total - left_total) * entropy(&right)) / total;
+        if best.as_ref().is_none_or(|(best, _, _)| remaining < *best) {
+            best = Some((remaining, split, left.clone()));
+        }
+    }
+    let (remaining, split, left) = match best {
+        Some(best) => best,
+        None => return,
+    };
+    let right: Vec<f64> = whole.iter().zip(left.iter()).map(|(all, left)| all - left).collect();
+    let gain = entropy(&whole) - remaining;
+    let delta = (3f64.powf(present(&whole)) - 2.).log2()
+        - (present(&whole) * entropy(&whole) - present(&left) * entropy(&left) - present(&right) * entropy(&right));
+    if total <= 1. || gain <= ((total - 1.).log2() + delta) / total {
+        return;
+    }
+    mdl_cuts(&values[..split], class_count, cuts);
+    cuts.push((values[split - 1].0 + values[split].0) / 2.);
+    mdl_cuts(&values[split..], class_count, cuts);
+}
@@ -1,18 +1,91 @@
+use crate::cost::CostMatrix;
 use crate::record::Record;
 use crate::decision::DecisionTree;
+use crate::regression::RegressionTree;
 
+/// Records without a class are left out of every score.
+fn scored(validation_data: &[Record]) -> impl Iterator<Item = &Record> + Clone {
+    validation_data.iter().filter(|record| record.class_known())
+}
+
+fn total_weight(validation_data: &[Record]) -> f64 {
+    scored(validation_data).map(|record| record.weight).sum()
+}
+
+/// Share of the validation weight that is classified correctly.
 pub fn evaluate(validation_data: &[Record], learner: &DecisionTree) -> f64 {
-    let mut correct = 0;
-    for record in validation_data.iter() {
+    let mut correct = 0.;
+    for record in scored(validation_data) {
         let predicted = learner.predict(record);
-        let value = record.class;
+        let value = record.class_index();
         let hit = value == predicted;
         if hit {
-            correct += 1;
+            correct += record.weight;
         }
     }
-    let accuracy = correct as f64 / validation_data.len() as f64;
-    accuracy
+    correct / total_weight(validation_data)
+}
+
+/// Average misclassification cost per unit of validation weight.
+pub fn expected_cost(validation_data: &[Record], learner: &DecisionTree, costs: &CostMatrix) -> f64 {
+    let total: f64 = scored(validation_data)
+        .map(|record| costs.cost(record.class_index(), learner.predict(record)) * record.weight)
+        .sum();
+    total / total_weight(validation_data)
 }
 
+/// Mean squared distance between the predicted class probabilities and the
+/// one-hot true class.
+pub fn brier_score(validation_data: &[Record], learner: &DecisionTree) -> f64 {
+    let total: f64 = scored(validation_data)
+        .map(|record| {
+            learner
+                .predict_proba(record)
+                .iter()
+                .enumerate()
+                .map(|(class, probability)| {
+                    let target = if class == record.class_index() { 1. } else { 0. };
+                    (probability - target) * (probability - target)
+                })
+                .sum::<f64>()
+                * record.weight
+        })
+        .sum();
+    total / total_weight(validation_data)
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct RegressionMetrics {
+    pub mae: f64,
+    pub rmse: f64,
+    /// Coefficient of determination against the validation mean
+    pub r2: f64,
+}
 
+/// Weighted mean absolute error, root mean squared error and R² on the
+/// records with a known target.
+pub fn evaluate_regression(validation_data: &[Record], learner: &RegressionTree) -> RegressionMetrics {
+    let total = total_weight(validation_data);
+    let mean = scored(validation_data).map(|record| record.target() * record.weight).sum::<f64>() / total;
+    let mut absolute_error = 0.;
+    let mut squared_error = 0.;
+    let mut total_squares = 0.;
+    for record in scored(validation_data) {
+        let error = learner.predict(record) - record.target();
+        absolute_error += record.weight * error.abs();
+        squared_error += record.weight * error * error;
+        total_squares += record.weight * (record.target() - mean) * (record.target() - mean);
+    }
+    let r2 = if total_squares > 0. {
+        1. - squared_error / total_squares
+    } else if squared_error == 0. {
+        1.
+    } else {
+        0.
+    };
+    RegressionMetrics {
+        mae: absolute_error / total,
+        rmse: (squared_error / total).sqrt(),
+        r2,
+    }
+}
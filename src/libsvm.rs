@@ -0,0 +1,196 @@
+use crate::arff::{Arff, ArffError, Class};
+use crate::record::{Features, Record, Value};
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+
+/// Reads a LibSVM (SVMlight) file of `label index:value ...` lines. Every
+/// feature index becomes a numeric attribute named after it, and the labels
+/// become a nominal class attribute after them. Features a line leaves out
+/// are 0, and `qid:` pairs and `#` comments are ignored.
+pub fn read<R: BufRead>(reader: R) -> Result<Arff, ArffError> {
+    let mut rows: Vec<(String, Vec<(usize, f64)>)> = Vec::new();
+    let mut feature_count = 0;
+    for (line_index, line) in reader.lines().enumerate() {
+        let line_number = line_index + 1;
+        let line = line.map_err(|err| ArffError::Io {
+            line: line_number,
+            message: err.to_string(),
+        })?;
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let label = match fields.next() {
+            Some(label) => label.to_owned(),
+            None => continue,
+        };
+        let mut features = Vec::new();
+        for (column, field) in fields.enumerate() {
+            let bad_entry = || ArffError::BadSparseEntry {
+                line: line_number,
+                column,
+                attribute: String::new(),
+                value: field.to_owned(),
+            };
+            let (index, value) = field.split_once(':').ok_or_else(bad_entry)?;
+            if index == "qid" {
+                continue;
+            }
+            let index = index.parse::<usize>().ok().filter(|index| *index > 0).ok_or_else(bad_entry)?;
+            let value = value
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| ArffError::BadNumber {
+                    line: line_number,
+                    column,
+                    attribute: index.to_string(),
+                    value: value.to_owned(),
+                })?;
+            feature_count = feature_count.max(index);
+            features.push((index - 1, value));
+        }
+        rows.push((label, features));
+    }
+    let mut values: Vec<String> = Vec::new();
+    for (label, _) in rows.iter() {
+        if !values.contains(label) {
+            values.push(label.clone());
+        }
+    }
+    // Numeric labels such as -1 and +1 read best in order, while labels
+    // like nan are names
+    let number = |value: &String| value.parse::<f64>().ok().filter(|number| number.is_finite());
+    if values.iter().all(|value| number(value).is_some()) {
+        values.sort_by(|x, y| number(x).unwrap().total_cmp(&number(y).unwrap()));
+    }
+    let target = feature_count;
+    let mut classes: Vec<Class> = (0..feature_count).map(|_| Class::Continuous).collect();
+    classes.push(Class::Nominal(values));
+    let mut defaults: Vec<Value> = vec![Value::Continuous(0.); feature_count];
+    defaults.push(Value::Missing);
+    let defaults = Arc::new(defaults);
+    let records = rows
+        .into_iter()
+        .map(|(label, mut features)| {
+            let class = match &classes[target] {
+                Class::Nominal(values) => Value::Nominal(values.iter().position(|value| *value == label).unwrap()),
+                _ => unreachable!(),
+            };
+            features.sort_by_key(|(index, _)| *index);
+            let mut values: Vec<(usize, Value)> = features
+                .into_iter()
+                .map(|(index, value)| (index, Value::Continuous(value)))
+                .collect();
+            values.push((target, class));
+            Record {
+                features: Features::Sparse {
+                    values,
+                    defaults: defaults.clone(),
+                },
+                class,
+                weight: 1.,
+            }
+        })
+        .collect();
+    Ok(Arff {
+        relation: String::new(),
+        records,
+        labels: (1..=feature_count).map(|index| index.to_string()).chain(Some(String::from("class"))).collect(),
+        classes,
+        target,
+        scaler: None,
+        discretizer: None,
+    })
+}
+
+/// Writes `data` in LibSVM format, numbering the other attributes from 1 in
+/// order. Nominal values are written as their index and string attributes
+/// are left out. Zeros and missing values are omitted, so missing reads back
+/// as 0, and records without a class are skipped. Fails on string classes,
+/// which have no LibSVM label.
+pub fn write<W: Write>(data: &Arff, writer: &mut W) -> io::Result<()> {
+    if let Class::String(_) = data.classes[data.target] {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "LibSVM labels can't be strings, use --strings-to-nominal",
+        ));
+    }
+    let features: Vec<usize> = (0..data.classes.len())
+        .filter(|index| *index != data.target && !matches!(data.classes[*index], Class::String(_)))
+        .collect();
+    for record in data.records.iter().filter(|record| record.class_known()) {
+        let label = match (record.class, &data.classes[data.target]) {
+            (Value::Nominal(index), Class::Nominal(values)) => values[index].replace(char::is_whitespace, "_"),
+            (Value::Continuous(value), _) => value.to_string(),
+            _ => unreachable!("string targets are rejected above"),
+        };
+        write!(writer, "{}", label)?;
+        for (number, index) in features.iter().enumerate() {
+            let value = match record.features[*index] {
+                Value::Nominal(value) => value as f64,
+                Value::Continuous(value) => value,
+                Value::Text(_) | Value::Missing => 0.,
+            };
+            if value != 0. {
+                write!(writer, " {}:{}", number + 1, value)?;
+            }
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(data: &Arff) -> Vec<String> {
+        match &data.classes[data.target] {
+            Class::Nominal(values) => values.clone(),
+            _ => panic!("expected a nominal class"),
+        }
+    }
+
+    #[test]
+    fn read_sparse_lines() {
+        let data = read("+1 qid:3 1:0.5 3:2 # comment\n\n-1 2:-1\n".as_bytes()).unwrap();
+        assert_eq!(data.labels, vec!["1", "2", "3", "class"]);
+        assert_eq!(labels(&data), vec!["-1", "+1"]);
+        assert_eq!(data.records.len(), 2);
+        let record = &data.records[0];
+        assert_eq!(
+            (0..3).map(|index| record.features[index]).collect::<Vec<Value>>(),
+            vec![Value::Continuous(0.5), Value::Continuous(0.), Value::Continuous(2.)]
+        );
+        assert_eq!(record.class, Value::Nominal(1));
+        assert_eq!(data.records[1].features[1], Value::Continuous(-1.));
+    }
+
+    #[test]
+    fn non_finite() {
+        for line in ["1 1:nan", "1 1:inf", "1 1:-inf"] {
+            match read(line.as_bytes()) {
+                Err(ArffError::BadNumber { line, column, .. }) => assert_eq!((line, column), (1, 0)),
+                other => panic!("expected a bad number for {}, got {:?}", line, other.err()),
+            }
+        }
+        // Labels are names, kept in order when not all numbers
+        let data = read("nan 1:1\n2 1:2\n1 1:3\n".as_bytes()).unwrap();
+        assert_eq!(labels(&data), vec!["nan", "2", "1"]);
+    }
+
+    #[test]
+    fn write_then_read() {
+        let data = read("b 1:1.5 4:-2\na 2:3\nb\n".as_bytes()).unwrap();
+        let mut written = Vec::new();
+        write(&data, &mut written).unwrap();
+        assert_eq!(String::from_utf8(written.clone()).unwrap(), "b 1:1.5 4:-2\na 2:3\nb\n");
+        let read_back = read(written.as_slice()).unwrap();
+        assert_eq!(read_back.classes.len(), data.classes.len());
+        for (read, record) in read_back.records.iter().zip(data.records.iter()) {
+            for index in 0..data.target {
+                assert_eq!(read.features[index], record.features[index]);
+            }
+            assert_eq!(read.class, record.class);
+        }
+    }
+}
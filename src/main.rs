@@ -1,11 +1,30 @@
 mod arff;
+mod cost;
+mod criterion;
+mod csv;
+mod date;
 mod decision;
+mod discretize;
 mod evaluate;
+mod libsvm;
 mod record;
+mod regression;
+mod scale;
+mod surrogate;
 
-use clap::{App, Arg};
-use evaluate::evaluate;
+use clap::{value_t, App, Arg};
+use arff::{write_header, write_record, Arff, ArffReader, Class, Prediction, Target};
+use cost::CostMatrix;
+use discretize::Binning;
+use csv::{ColumnKind, CsvOptions};
+use evaluate::{brier_score, evaluate, evaluate_regression, expected_cost, RegressionMetrics};
+use record::{Record, Value};
+use regression::RegressionTree;
+use scale::Scaling;
 use std::fs;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::process;
 
 fn main() {
     let matches = App::new("decision tree")
@@ -15,12 +34,68 @@ fn main() {
             Arg::with_name("file")
                 .short("f")
                 .long("file")
+                .help("ARFF file, or - for stdin")
                 .required(true)
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .help("input format, by default csv for .csv, libsvm for .libsvm and .svm files and arff otherwise")
+                .takes_value(true)
+                .possible_values(&["arff", "csv", "libsvm"]),
+        )
+        .arg(
+            Arg::with_name("output-format")
+                .long("output-format")
+                .help("format of --output, by default libsvm for .libsvm and .svm files and arff otherwise")
+                .takes_value(true)
+                .possible_values(&["arff", "libsvm"]),
+        )
+        .arg(
+            Arg::with_name("max-nominal")
+                .long("max-nominal")
+                .help("CSV columns of whole numbers with at most this many values are nominal")
+                .takes_value(true)
+                .default_value("10"),
+        )
+        .arg(
+            Arg::with_name("nominal")
+                .long("nominal")
+                .help("comma-separated CSV columns to read as nominal")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("numeric")
+                .long("numeric")
+                .help("comma-separated CSV columns to read as numeric")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .short("o")
+                .help("write the data, or the predicted records in predict mode, as ARFF to a file or - for stdout")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("scored")
+                .long("scored")
+                .help("append the predicted class and class probabilities to --output")
+                .requires("output"),
+        )
+        .arg(
+            Arg::with_name("class")
+                .long("class")
+                .short("c")
+                .help("class attribute: first, last, a 1-based index or a name")
+                .takes_value(true)
+                .default_value("last"),
+        )
         .arg(
             Arg::with_name("validation")
                 .short("v")
+                .help("random <percent>, training <depth>, cross <folds> or predict <ARFF file or ->")
                 .required(true)
                 .number_of_values(2)
                 .takes_value(true),
@@ -32,18 +107,289 @@ fn main() {
                 .takes_value(true),
         )
         .arg(Arg::with_name("prune").long("prune").short("p"))
+        .arg(
+            Arg::with_name("strings-to-nominal")
+                .long("strings-to-nominal")
+                .help("learn from string attributes as nominal ones"),
+        )
+        .arg(Arg::with_name("gain-ratio").long("gain-ratio").short("g"))
+        .arg(
+            Arg::with_name("criterion")
+                .long("criterion")
+                .takes_value(true)
+                .possible_values(&["entropy", "gini", "misclassification"])
+                .default_value("entropy"),
+        )
+        .arg(
+            Arg::with_name("tie-break")
+                .long("tie-break")
+                .takes_value(true)
+                .possible_values(&["parent", "prior", "lowest"])
+                .default_value("parent"),
+        )
+        .arg(
+            Arg::with_name("max-depth")
+                .long("max-depth")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("min-samples-split")
+                .long("min-samples-split")
+                .takes_value(true)
+                .default_value("2"),
+        )
+        .arg(
+            Arg::with_name("min-samples-leaf")
+                .long("min-samples-leaf")
+                .takes_value(true)
+                .default_value("1"),
+        )
+        .arg(
+            Arg::with_name("min-gain")
+                .long("min-gain")
+                .takes_value(true)
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("max-leaf-nodes")
+                .long("max-leaf-nodes")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("smoothing")
+                .long("smoothing")
+                .help("none, laplace or m=<m>")
+                .takes_value(true)
+                .default_value("none"),
+        )
+        .arg(
+            Arg::with_name("missing")
+                .long("missing")
+                .help("prediction at a branch with no path for the value, tried after surrogates; distribute keeps no surrogates")
+                .takes_value(true)
+                .possible_values(&["majority", "distribute"])
+                .default_value("majority"),
+        )
+        .arg(
+            Arg::with_name("surrogates")
+                .long("surrogates")
+                .help("surrogate splits kept per branch, tried before --missing; ignored with --missing distribute")
+                .takes_value(true)
+                .default_value("3"),
+        )
+        .arg(
+            Arg::with_name("costs")
+                .long("costs")
+                .help("file of <actual> <predicted> <cost> lines")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("reweight")
+                .long("reweight")
+                .help("weight training records by their cost instead of labelling leaves by it")
+                .requires("costs"),
+        )
+        .arg(
+            Arg::with_name("class-weights")
+                .long("class-weights")
+                .help("none, balanced or one weight per class value, e.g. 1,4.5")
+                .takes_value(true)
+                .default_value("none"),
+        )
+        .arg(
+            Arg::with_name("scale")
+                .long("scale")
+                .help("Rescales numeric attributes, fitted to the training data and applied to predicted data")
+                .takes_value(true)
+                .possible_values(&["none", "normalize", "standardize"])
+                .default_value("none"),
+        )
+        .arg(
+            Arg::with_name("discretize")
+                .long("discretize")
+                .help("Turns numeric attributes into intervals: mdl, width=<bins> or frequency=<bins>")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("regression-criterion")
+                .long("regression-criterion")
+                .takes_value(true)
+                .possible_values(&["variance", "mse", "mae"])
+                .default_value("variance"),
+        )
+        .arg(
+            Arg::with_name("leaf-prediction")
+                .long("leaf-prediction")
+                .takes_value(true)
+                .possible_values(&["mean", "median"])
+                .default_value("mean"),
+        )
         .get_matches();
     let file = matches.value_of("file").unwrap();
     let should_prune = matches.is_present("prune");
 
-    use self::arff::Arff;
-    let mut data =
-        Arff::parse(&fs::read_to_string(file).expect("file not found")).expect("parse failed");
-    // data.normalize();
-    data.shuffle();
+    let target = value_t!(matches, "class", Target).unwrap_or_else(|e| e.exit());
+    let format = format_of(file, matches.value_of("format"));
+    let loaded = if format == "libsvm" {
+        libsvm::read(open_input(file)).map(|mut data| {
+            data.relation = file_stem(file);
+            data
+        })
+    } else if format == "csv" {
+        let mut options = CsvOptions {
+            max_nominal_values: value_t!(matches, "max-nominal", usize).unwrap_or_else(|e| e.exit()),
+            ..CsvOptions::default()
+        };
+        for (arg, kind) in [("nominal", ColumnKind::Nominal), ("numeric", ColumnKind::Numeric)] {
+            for column in matches.value_of(arg).into_iter().flat_map(|columns| columns.split(',')) {
+                options.overrides.push((column.trim().to_owned(), kind));
+            }
+        }
+        csv::read(open_input(file), &target, &options).map(|mut data| {
+            data.relation = file_stem(file);
+            data
+        })
+    } else {
+        Arff::read(open_input(file), &target)
+    };
+    let mut data = match loaded {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("{}: {}", file, err);
+            process::exit(1);
+        }
+    };
+    if matches.is_present("strings-to-nominal") {
+        data.strings_to_nominal();
+    }
+    let preprocessing = Preprocessing {
+        scaling: matches
+            .value_of("scale")
+            .filter(|scaling| *scaling != "none")
+            .map(|scaling| scaling.parse().unwrap()),
+        binning: if matches.is_present("discretize") {
+            Some(value_t!(matches, "discretize", Binning).unwrap_or_else(|e| e.exit()))
+        } else {
+            None
+        },
+    };
     // dbg!(&data.records);
-    let mut learner = decision::DecisionTree::default();
+    let measure = if matches.is_present("gain-ratio") {
+        decision::SplitMeasure::GainRatio
+    } else {
+        decision::SplitMeasure::InformationGain
+    };
+    let criterion = value_t!(matches, "criterion", criterion::Criterion).unwrap_or_else(|e| e.exit());
+    let tie_break = value_t!(matches, "tie-break", decision::TieBreak).unwrap_or_else(|e| e.exit());
+    let mut config = decision::TreeConfig::default()
+        .measure(measure)
+        .criterion(criterion)
+        .tie_break(tie_break)
+        .min_samples_split(value_t!(matches, "min-samples-split", usize).unwrap_or_else(|e| e.exit()))
+        .min_samples_leaf(value_t!(matches, "min-samples-leaf", usize).unwrap_or_else(|e| e.exit()))
+        .min_information_gain(value_t!(matches, "min-gain", f64).unwrap_or_else(|e| e.exit()))
+        .smoothing(value_t!(matches, "smoothing", decision::Smoothing).unwrap_or_else(|e| e.exit()))
+        .missing_values(value_t!(matches, "missing", decision::MissingValues).unwrap_or_else(|e| e.exit()))
+        .surrogates(value_t!(matches, "surrogates", usize).unwrap_or_else(|e| e.exit()))
+        .regression_criterion(
+            value_t!(matches, "regression-criterion", regression::RegressionCriterion).unwrap_or_else(|e| e.exit()),
+        )
+        .leaf_prediction(value_t!(matches, "leaf-prediction", regression::LeafPrediction).unwrap_or_else(|e| e.exit()));
+    if matches.is_present("max-depth") {
+        config = config.max_depth(value_t!(matches, "max-depth", usize).unwrap_or_else(|e| e.exit()));
+    }
+    if matches.is_present("max-leaf-nodes") {
+        config = config.max_leaf_nodes(value_t!(matches, "max-leaf-nodes", usize).unwrap_or_else(|e| e.exit()));
+    }
+    let costs = matches.value_of("costs").map(|costs_file| {
+        let class_values = match data.classes.get(data.target) {
+            Some(Class::Nominal(values)) => values.as_slice(),
+            _ => {
+                eprintln!("{}: costs need a nominal target", costs_file);
+                process::exit(1);
+            }
+        };
+        let contents = fs::read_to_string(costs_file).unwrap_or_else(|err| {
+            eprintln!("{}: {}", costs_file, err);
+            process::exit(1);
+        });
+        match CostMatrix::parse(&contents, class_values) {
+            Ok(costs) => costs,
+            Err(err) => {
+                eprintln!("{}: {}", costs_file, err);
+                process::exit(1);
+            }
+        }
+    });
+    let class_weights = value_t!(matches, "class-weights", decision::ClassWeights).unwrap_or_else(|e| e.exit());
+    if let (decision::ClassWeights::Explicit(weights), Some(Class::Nominal(values))) = (&class_weights, data.classes.get(data.target)) {
+        if weights.len() != values.len() {
+            eprintln!("expected {} class weights, found {}", values.len(), weights.len());
+            process::exit(1);
+        }
+    }
+    config = config.class_weights(class_weights);
+    if let Some(costs) = &costs {
+        config = config.cost_matrix(costs.clone()).reweight(matches.is_present("reweight"));
+    }
     let mut validation_values = matches.values_of("validation").unwrap();
+    let predicting = matches.values_of("validation").unwrap().next() == Some("predict");
+    let scored = matches.is_present("scored");
+    let libsvm_output = matches
+        .value_of("output")
+        .is_some_and(|output| format_of(output, matches.value_of("output-format")) == "libsvm");
+    if libsvm_output && (predicting || scored) {
+        eprintln!("predicted and scored records are only written as ARFF");
+        process::exit(1);
+    }
+    let mut output = matches.value_of("output").map(create_output);
+    if let Some(writer) = output.as_mut().filter(|_| !predicting) {
+        // Written data is preprocessed as a whole, like data to predict on
+        let mut written = data.with_records(data.records.clone());
+        preprocessing.apply(&mut written);
+        if libsvm_output {
+            libsvm::write(&written, writer)
+                .and_then(|_| writer.flush())
+                .unwrap_or_else(|err| write_failed(&err));
+        } else {
+            let predictions: Option<Vec<Prediction>> = match &written.classes[written.target] {
+                Class::Nominal(_) if scored => {
+                    let mut learner = decision::DecisionTree::new(config.clone());
+                    learner.train(written.records.as_slice(), &written.classes, written.target);
+                    Some(
+                        written
+                            .records
+                            .iter()
+                            .map(|record| (learner.predict(record), learner.predict_proba(record)))
+                            .collect(),
+                    )
+                }
+                _ if scored => {
+                    eprintln!("{}: --scored needs a nominal target", file);
+                    process::exit(1);
+                }
+                _ => None,
+            };
+            written
+                .write(writer, predictions.as_deref())
+                .and_then(|_| writer.flush())
+                .unwrap_or_else(|err| write_failed(&err));
+        }
+    }
+    data.shuffle();
+    match data.classes.get(data.target) {
+        Some(Class::Continuous) | Some(Class::Date(_)) => {
+            let learner = RegressionTree::new(config);
+            run_regression(&mut data, &target, learner, &preprocessing, validation_values, output);
+            return;
+        }
+        Some(Class::String(_)) => {
+            eprintln!("{}: string targets need --strings-to-nominal", file);
+            process::exit(1);
+        }
+        _ => {}
+    }
+    let mut learner = decision::DecisionTree::new(config);
     match validation_values.next() {
         Some("random") => {
             if let Some(training_size) = validation_values
@@ -52,49 +398,77 @@ fn main() {
             {
                 let training_count = (data.records.len() as f64 * (training_size / 100.0)) as usize;
                 let (train, test) = data.records.split_at(training_count);
-                learner.train(train, &data.classes);
-                let accuracy = evaluate(test, &learner);
+                let (train, test) = preprocessing.split(&data, train, test);
+                learner.train(&train.records, &train.classes, train.target);
+                let accuracy = evaluate(&test, &learner);
                 println!("test accuracy: {}", accuracy);
+                println!("test brier score: {}", brier_score(&test, &learner));
+                if let Some(costs) = &costs {
+                    println!("test expected cost: {}", expected_cost(&test, &learner, costs));
+                }
+            }
+        }
+        Some("predict") => {
+            preprocessing.apply(&mut data);
+            learner.train(data.records.as_slice(), &data.classes, data.target);
+            let path = validation_values.next().unwrap();
+            match output {
+                Some(mut writer) => {
+                    write_header(&mut writer, &data.relation, &data.labels, &data.classes, data.target, scored)
+                        .unwrap_or_else(|err| write_failed(&err));
+                    for_each_streamed(&mut data, path, &target, |mut record, data| {
+                        let prediction = (learner.predict(&record), learner.predict_proba(&record));
+                        // Scored records keep their class next to the prediction
+                        let prediction = if scored {
+                            Some(&prediction)
+                        } else {
+                            record.class = Value::Nominal(prediction.0);
+                            None
+                        };
+                        write_record(&mut writer, &record, &data.classes, data.target, prediction)
+                            .unwrap_or_else(|err| write_failed(&err));
+                    });
+                    writer.flush().unwrap_or_else(|err| write_failed(&err));
+                }
+                None => for_each_streamed(&mut data, path, &target, |record, data| {
+                    if let Class::Nominal(values) = &data.classes[data.target] {
+                        println!("{}", values[learner.predict(&record)]);
+                    }
+                }),
             }
         }
         Some("training") => {
-            learner.train(data.records.as_slice(), &data.classes);
-            learner.print_tree(10, &data.classes, &data.labels);
-            // dbg!(learner);
+            if let Some(depth) = validation_values
+                .next()
+                .and_then(|val| val.parse::<usize>().ok())
+            {
+                preprocessing.apply(&mut data);
+                learner.train(data.records.as_slice(), &data.classes, data.target);
+                learner.print_tree(depth, &data.classes, &data.labels);
+                println!("undecided leaves: {}", learner.training_stats().undecided_leaves);
+                // dbg!(learner);
+            }
         }
         Some("cross") => {
             if let Some(fold_count) = validation_values
                 .next()
                 .and_then(|val| val.parse::<usize>().ok())
             {
-                let chunk_size = (data.records.len() as f64 / fold_count as f64).ceil() as usize;
-                let results: Vec<(f64, usize, usize)> = (0..data.records.len())
-                    .step_by(chunk_size)
-                    .map(|chunk_start| {
-                        let mut training_data = Vec::with_capacity(data.records.len() - chunk_size);
-                        if chunk_start > 0 {
-                            training_data.extend_from_slice(&data.records[0..chunk_start]);
-                        }
-                        if chunk_start + chunk_size < data.records.len() {
-                            training_data.extend_from_slice(
-                                &data.records[chunk_start + chunk_size..data.records.len()],
-                            );
-                        }
+                let results: Vec<(f64, usize, usize, usize, f64)> = cross_folds(&data.records, fold_count)
+                    .into_iter()
+                    .map(|(training_data, test_data)| {
+                        let (training_data, test_data) = preprocessing.split(&data, &training_data, test_data);
                         if should_prune {
-                            let training_count = (training_data.len() as f32 * 0.7) as usize;
-                            let (training, validation) = training_data.split_at(training_count);
-                            learner.train(training, &data.classes);
+                            let training_count = (training_data.records.len() as f32 * 0.7) as usize;
+                            let (training, validation) = training_data.records.split_at(training_count);
+                            learner.train(training, &training_data.classes, data.target);
                             learner.prune(validation);
                         } else {
-                            learner.train(training_data.as_slice(), &data.classes);
+                            learner.train(&training_data.records, &training_data.classes, data.target);
                         }
-                        let end_index = if chunk_start + chunk_size > data.records.len() {
-                            data.records.len()
-                        } else {
-                            chunk_start + chunk_size
-                        };
-                        (evaluate(&data.records[chunk_start..end_index], &learner), learner.count_live_nodes(), learner.max_depth())
-                        // (evaluate(&data.records[chunk_start..end_index], &learner), 0, 0)
+                        let cost = costs.as_ref().map_or(0., |costs| expected_cost(&test_data, &learner, costs));
+                        (evaluate(&test_data, &learner), learner.count_live_nodes(), learner.max_depth(), learner.training_stats().undecided_leaves, cost)
+                        // (evaluate(test_data, &learner), 0, 0)
                     })
                     .collect();
                 let average_accuracy =
@@ -106,6 +480,211 @@ fn main() {
                 let max_depth =
                     results.iter().fold(0, |acc, x| acc + x.2) / fold_count;
                 dbg!(max_depth);
+                let undecided_leaves = results.iter().fold(0, |acc, x| acc + x.3);
+                dbg!(undecided_leaves);
+                if costs.is_some() {
+                    let average_cost = results.iter().map(|x| x.4).sum::<f64>() / fold_count as f64;
+                    dbg!(average_cost);
+                }
+            }
+        }
+        _ => panic!("unknown validation type"),
+    }
+}
+
+/// Scaling and discretization fitted to training data only, so the records
+/// a model is tested on can't leak into them.
+#[derive(Debug, Default)]
+struct Preprocessing {
+    scaling: Option<Scaling>,
+    binning: Option<Binning>,
+}
+
+impl Preprocessing {
+    /// Fits to all of `data` and transforms it.
+    fn apply(&self, data: &mut Arff) {
+        if let Some(scaling) = self.scaling {
+            data.scale(scaling);
+        }
+        if let Some(binning) = self.binning {
+            if let Err(err) = data.discretize(binning) {
+                eprintln!("{}", err);
+                process::exit(1);
+            }
+        }
+    }
+
+    /// `training` as a dataset like `data` with the preprocessing fitted to
+    /// it, and `test` transformed the same way.
+    fn split(&self, data: &Arff, training: &[Record], test: &[Record]) -> (Arff, Vec<Record>) {
+        let mut training = data.with_records(training.to_vec());
+        self.apply(&mut training);
+        let mut test = test.to_vec();
+        training.transform(&mut test);
+        (training, test)
+    }
+}
+
+/// Training records and test slice of every fold.
+fn cross_folds(records: &[Record], fold_count: usize) -> Vec<(Vec<Record>, &[Record])> {
+    let chunk_size = (records.len() as f64 / fold_count as f64).ceil() as usize;
+    (0..records.len())
+        .step_by(chunk_size)
+        .map(|chunk_start| {
+            let end_index = (chunk_start + chunk_size).min(records.len());
+            let mut training_data = Vec::with_capacity(records.len() - (end_index - chunk_start));
+            training_data.extend_from_slice(&records[0..chunk_start]);
+            training_data.extend_from_slice(&records[end_index..records.len()]);
+            (training_data, &records[chunk_start..end_index])
+        })
+        .collect()
+}
+
+/// Buffered reader of `path`, or of stdin for `-`.
+fn open_input(path: &str) -> Box<dyn BufRead> {
+    if path == "-" {
+        return Box::new(BufReader::new(io::stdin()));
+    }
+    match File::open(path) {
+        Ok(file) => Box::new(BufReader::new(file)),
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            process::exit(1);
+        }
+    }
+}
+
+/// `explicit` format, or the one the extension of `path` suggests.
+fn format_of<'a>(path: &str, explicit: Option<&'a str>) -> &'a str {
+    let path = path.to_lowercase();
+    explicit.unwrap_or(if path.ends_with(".csv") {
+        "csv"
+    } else if path.ends_with(".libsvm") || path.ends_with(".svm") {
+        "libsvm"
+    } else {
+        "arff"
+    })
+}
+
+/// Name of the file at `path` without directories or extension.
+fn file_stem(path: &str) -> String {
+    std::path::Path::new(path)
+        .file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned())
+}
+
+/// Buffered writer of `path`, or of stdout for `-`.
+fn create_output(path: &str) -> Box<dyn Write> {
+    if path == "-" {
+        return Box::new(BufWriter::new(io::stdout()));
+    }
+    match File::create(path) {
+        Ok(file) => Box::new(BufWriter::new(file)),
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            process::exit(1);
+        }
+    }
+}
+
+fn write_failed(err: &io::Error) -> ! {
+    eprintln!("writing output: {}", err);
+    process::exit(1);
+}
+
+/// Streams the records of the ARFF file at `path`, which must declare the
+/// same attributes as `data`, into `f` one at a time, after conforming them
+/// to `data`.
+fn for_each_streamed(data: &mut Arff, path: &str, target: &Target, mut f: impl FnMut(Record, &Arff)) {
+    let fail = |err: &dyn std::fmt::Display| -> ! {
+        eprintln!("{}: {}", path, err);
+        process::exit(1);
+    };
+    let mut reader = ArffReader::new(open_input(path), target).unwrap_or_else(|err| fail(&err));
+    if reader.labels != data.labels {
+        fail(&"attributes differ from the training data");
+    }
+    data.check_attributes(&reader.classes).unwrap_or_else(|err| fail(&err));
+    while let Some(record) = reader.next() {
+        let mut record = record.unwrap_or_else(|err| fail(&err));
+        data.conform(&mut record, &reader.classes);
+        f(record, data);
+    }
+}
+
+fn run_regression<'a>(
+    data: &mut Arff,
+    target: &Target,
+    mut learner: RegressionTree,
+    preprocessing: &Preprocessing,
+    mut validation_values: impl Iterator<Item = &'a str>,
+    output: Option<Box<dyn Write>>,
+) {
+    match validation_values.next() {
+        Some("random") => {
+            if let Some(training_size) = validation_values
+                .next()
+                .and_then(|val| val.parse::<f64>().ok())
+            {
+                let training_count = (data.records.len() as f64 * (training_size / 100.0)) as usize;
+                let (train, test) = data.records.split_at(training_count);
+                let (train, test) = preprocessing.split(data, train, test);
+                learner.train(&train.records, &train.classes, train.target);
+                let metrics = evaluate_regression(&test, &learner);
+                println!("test mae: {}", metrics.mae);
+                println!("test rmse: {}", metrics.rmse);
+                println!("test r2: {}", metrics.r2);
+            }
+        }
+        Some("predict") => {
+            preprocessing.apply(data);
+            learner.train(data.records.as_slice(), &data.classes, data.target);
+            let path = validation_values.next().unwrap();
+            match output {
+                Some(mut writer) => {
+                    write_header(&mut writer, &data.relation, &data.labels, &data.classes, data.target, false)
+                        .unwrap_or_else(|err| write_failed(&err));
+                    for_each_streamed(data, path, target, |mut record, data| {
+                        record.class = Value::Continuous(learner.predict(&record));
+                        write_record(&mut writer, &record, &data.classes, data.target, None)
+                            .unwrap_or_else(|err| write_failed(&err));
+                    });
+                    writer.flush().unwrap_or_else(|err| write_failed(&err));
+                }
+                None => for_each_streamed(data, path, target, |record, _| {
+                    println!("{}", learner.predict(&record));
+                }),
+            }
+        }
+        Some("training") => {
+            if let Some(depth) = validation_values
+                .next()
+                .and_then(|val| val.parse::<usize>().ok())
+            {
+                preprocessing.apply(data);
+                learner.train(data.records.as_slice(), &data.classes, data.target);
+                learner.print_tree(depth, &data.classes, &data.labels);
+            }
+        }
+        Some("cross") => {
+            if let Some(fold_count) = validation_values
+                .next()
+                .and_then(|val| val.parse::<usize>().ok())
+            {
+                let results: Vec<RegressionMetrics> = cross_folds(&data.records, fold_count)
+                    .into_iter()
+                    .map(|(training_data, test_data)| {
+                        let (training_data, test_data) = preprocessing.split(data, &training_data, test_data);
+                        learner.train(&training_data.records, &training_data.classes, data.target);
+                        evaluate_regression(&test_data, &learner)
+                    })
+                    .collect();
+                let average_mae = results.iter().map(|x| x.mae).sum::<f64>() / fold_count as f64;
+                dbg!(average_mae);
+                let average_rmse = results.iter().map(|x| x.rmse).sum::<f64>() / fold_count as f64;
+                dbg!(average_rmse);
+                let average_r2 = results.iter().map(|x| x.r2).sum::<f64>() / fold_count as f64;
+                dbg!(average_r2);
             }
         }
         _ => panic!("unknown validation type"),
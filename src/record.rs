@@ -1,5 +1,131 @@
-#[derive(Debug, Default, Clone)]
+use std::ops::Index;
+use std::sync::Arc;
+
+/// A single attribute value: an index into a nominal attribute's value list,
+/// the raw reading of a continuous or date attribute, an index into a string
+/// attribute's values, or `?`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Nominal(usize),
+    Continuous(f64),
+    Text(usize),
+    Missing,
+}
+
+/// The feature values of a record, read with `features[index]`.
+#[derive(Debug, Clone)]
+pub enum Features {
+    Dense(Vec<Value>),
+    /// Values that differ from the attribute defaults, sorted by index, so
+    /// rows of mostly zeros only store what they have
+    Sparse {
+        values: Vec<(usize, Value)>,
+        defaults: Arc<Vec<Value>>,
+    },
+}
+
+impl Index<usize> for Features {
+    type Output = Value;
+
+    fn index(&self, index: usize) -> &Value {
+        match self {
+            Features::Dense(values) => &values[index],
+            Features::Sparse { values, defaults } => {
+                match values.binary_search_by_key(&index, |(feature, _)| *feature) {
+                    Ok(position) => &values[position].1,
+                    Err(_) => &defaults[index],
+                }
+            }
+        }
+    }
+}
+
+impl Features {
+    /// Replaces every value, including the defaults of a sparse row, with
+    /// `f(index, value)`.
+    pub fn map(&mut self, mut f: impl FnMut(usize, Value) -> Value) {
+        match self {
+            Features::Dense(values) => {
+                for (index, value) in values.iter_mut().enumerate() {
+                    *value = f(index, *value);
+                }
+            }
+            Features::Sparse { values, defaults } => {
+                for (index, value) in values.iter_mut() {
+                    *value = f(*index, *value);
+                }
+                for (index, value) in Arc::make_mut(defaults).iter_mut().enumerate() {
+                    *value = f(index, *value);
+                }
+            }
+        }
+    }
+}
+
+/// Replaces every feature value of `records` with `f(index, value)`. Sparse
+/// records that shared their defaults still share the replaced ones.
+pub fn map_features(records: &mut [Record], mut f: impl FnMut(usize, Value) -> Value) {
+    // Original defaults, kept alive so pointers can't be reused, and their
+    // replacement
+    let mut mapped: Vec<[Arc<Vec<Value>>; 2]> = Vec::new();
+    for record in records.iter_mut() {
+        match &mut record.features {
+            Features::Dense(_) => record.features.map(&mut f),
+            Features::Sparse { values, defaults } => {
+                for (index, value) in values.iter_mut() {
+                    *value = f(*index, *value);
+                }
+                let replacement = match mapped.iter().find(|[original, _]| Arc::ptr_eq(original, defaults)) {
+                    Some([_, replacement]) => replacement.clone(),
+                    None => {
+                        let replacement: Arc<Vec<Value>> =
+                            Arc::new(defaults.iter().enumerate().map(|(index, value)| f(index, *value)).collect());
+                        mapped.push([defaults.clone(), replacement.clone()]);
+                        replacement
+                    }
+                };
+                *defaults = replacement;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Record {
-    pub features: Vec<usize>,
-    pub class: usize,
+    /// Every attribute of the row, indexed like the ARFF header. Learners
+    /// skip the target column.
+    pub features: Features,
+    /// Value of the target attribute
+    pub class: Value,
+    /// How many instances the record stands for, 1 unless the ARFF line ends
+    /// with a `{weight}`
+    pub weight: f64,
+}
+
+impl Record {
+    /// Records whose class is `?` can be predicted but not learned from or
+    /// scored against.
+    pub fn class_known(&self) -> bool {
+        self.class != Value::Missing
+    }
+
+    /// Index of a nominal class, as learned by `DecisionTree`.
+    pub fn class_index(&self) -> usize {
+        match self.class {
+            Value::Nominal(index) => index,
+            Value::Continuous(_) => unreachable!("numeric targets are learned by RegressionTree"),
+            Value::Text(_) => unreachable!("string targets can't be learned"),
+            Value::Missing => unreachable!("records without a class are filtered out"),
+        }
+    }
+
+    /// Value of a numeric target, as learned by `RegressionTree`.
+    pub fn target(&self) -> f64 {
+        match self.class {
+            Value::Continuous(value) => value,
+            Value::Nominal(_) => unreachable!("nominal targets are learned by DecisionTree"),
+            Value::Text(_) => unreachable!("string targets can't be learned"),
+            Value::Missing => unreachable!("records without a target are filtered out"),
+        }
+    }
 }
@@ -0,0 +1,363 @@
+use crate::arff::Class;
+use crate::decision::{attach, candidate_features, distribute_missing, path_key, print_levels, Frontier, PrintedNode, TreeConfig, TreeNode};
+use crate::record::{Record, Value};
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Impurity of the numeric targets at a node.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum RegressionCriterion {
+    /// Mean squared error around the mean, splits maximise variance reduction
+    #[default]
+    Variance,
+    /// Mean absolute error around the median
+    Mae,
+}
+
+impl FromStr for RegressionCriterion {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "variance" | "mse" => Ok(RegressionCriterion::Variance),
+            "mae" => Ok(RegressionCriterion::Mae),
+            _ => Err(format!("unknown regression criterion {}", name)),
+        }
+    }
+}
+
+/// A target value and the weight of the record it belongs to.
+type Weighted = (f64, f64);
+
+fn total_weight(targets: &[Weighted]) -> f64 {
+    targets.iter().map(|(_, weight)| weight).sum()
+}
+
+impl RegressionCriterion {
+    fn impurity(self, targets: &[Weighted]) -> f64 {
+        let total = total_weight(targets);
+        if total <= 0. {
+            return 0.;
+        }
+        let center = match self {
+            RegressionCriterion::Variance => mean(targets),
+            RegressionCriterion::Mae => median(targets),
+        };
+        targets
+            .iter()
+            .map(|(target, weight)| match self {
+                RegressionCriterion::Variance => weight * (target - center) * (target - center),
+                RegressionCriterion::Mae => weight * (target - center).abs(),
+            })
+            .sum::<f64>()
+            / total
+    }
+
+    /// Impurity left after a split, weighted by child weight.
+    fn remaining(self, children: &[Vec<Weighted>], total_size: f64) -> f64 {
+        children
+            .iter()
+            .map(|child| self.impurity(child) * (total_weight(child) / total_size))
+            .sum()
+    }
+}
+
+/// What a leaf predicts from the targets that reached it.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum LeafPrediction {
+    #[default]
+    Mean,
+    Median,
+}
+
+impl FromStr for LeafPrediction {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "mean" => Ok(LeafPrediction::Mean),
+            "median" => Ok(LeafPrediction::Median),
+            _ => Err(format!("unknown leaf prediction {}", name)),
+        }
+    }
+}
+
+impl LeafPrediction {
+    fn predict(self, targets: &[Weighted]) -> f64 {
+        match self {
+            LeafPrediction::Mean => mean(targets),
+            LeafPrediction::Median => median(targets),
+        }
+    }
+}
+
+/// Weighted mean of `values`, 0 when they weigh nothing.
+fn mean(values: &[Weighted]) -> f64 {
+    let total = total_weight(values);
+    if total <= 0. {
+        return 0.;
+    }
+    values.iter().map(|(value, weight)| value * weight).sum::<f64>() / total
+}
+
+/// Weighted median of `values`, 0 when they weigh nothing. When the weight
+/// splits exactly in half between two values, their midpoint.
+fn median(values: &[Weighted]) -> f64 {
+    let total = total_weight(values);
+    if total <= 0. {
+        return 0.;
+    }
+    let mut sorted: Vec<Weighted> = values.iter().filter(|(_, weight)| *weight > 0.).cloned().collect();
+    sorted.sort_by(|x, y| x.0.total_cmp(&y.0));
+    let mut seen = 0.;
+    for (index, (value, weight)) in sorted.iter().enumerate() {
+        seen += weight;
+        if seen * 2. == total {
+            return (value + sorted[index + 1].0) / 2.;
+        }
+        if seen * 2. > total {
+            return *value;
+        }
+    }
+    sorted.last().unwrap().0
+}
+
+#[derive(Debug, Default)]
+struct RegressionBranch {
+    paths: HashMap<usize, usize>,
+    feature: usize,
+    threshold: Option<f64>,
+    /// Prediction for records without a path
+    value: f64,
+}
+
+#[derive(Debug, Default)]
+struct RegressionLeaf {
+    value: f64,
+}
+
+#[derive(Debug)]
+enum RegressionNode {
+    Branch(RegressionBranch),
+    Leaf(RegressionLeaf),
+}
+
+impl TreeNode for RegressionNode {
+    fn paths_mut(&mut self) -> Option<&mut HashMap<usize, usize>> {
+        match self {
+            RegressionNode::Branch(branch) => Some(&mut branch.paths),
+            RegressionNode::Leaf(_) => None,
+        }
+    }
+}
+
+/// A candidate split with the targets of every child.
+struct Split {
+    feature: usize,
+    threshold: Option<f64>,
+    children: Vec<Vec<Weighted>>,
+}
+
+/// Best `value <= threshold` split of a continuous feature, trying the midpoint
+/// between every pair of adjacent distinct values.
+fn best_threshold(
+    criterion: RegressionCriterion,
+    min_samples_leaf: usize,
+    sub_set: &[(&Record, f64)],
+    feature: usize,
+) -> Option<Split> {
+    let mut values: Vec<(f64, Weighted)> = sub_set
+        .iter()
+        .filter_map(|(record, weight)| match record.features[feature] {
+            Value::Continuous(value) => Some((value, (record.target(), *weight))),
+            _ => None,
+        })
+        .collect();
+    values.sort_by(|x, y| x.0.total_cmp(&y.0));
+    let targets: Vec<Weighted> = values.iter().map(|(_, target)| *target).collect();
+    let total_size = total_weight(&targets);
+    // Running sums make the variance of every prefix and suffix O(1)
+    let total_sum: f64 = targets.iter().map(|(target, weight)| weight * target).sum();
+    let total_squares: f64 = targets.iter().map(|(target, weight)| weight * target * target).sum();
+    let (mut size, mut sum, mut squares) = (0., 0., 0.);
+    let mut best: Option<(f64, usize)> = None;
+    for index in 1..targets.len() {
+        let (target, weight) = targets[index - 1];
+        size += weight;
+        sum += weight * target;
+        squares += weight * target * target;
+        let above_size = total_size - size;
+        if values[index - 1].0 == values[index].0
+            || size < min_samples_leaf as f64
+            || above_size < min_samples_leaf as f64
+        {
+            continue;
+        }
+        let score = match criterion {
+            RegressionCriterion::Variance => {
+                let below_error = squares - sum * sum / size;
+                let above_error =
+                    (total_squares - squares) - (total_sum - sum) * (total_sum - sum) / above_size;
+                (below_error + above_error) / total_size
+            }
+            RegressionCriterion::Mae => {
+                let children = [targets[..index].to_vec(), targets[index..].to_vec()];
+                criterion.remaining(&children, total_size)
+            }
+        };
+        if best.is_none_or(|(best_score, _)| score < best_score) {
+            best = Some((score, index));
+        }
+    }
+    best.map(|(_, index)| Split {
+        feature,
+        threshold: Some((values[index - 1].0 + values[index].0) / 2.),
+        children: vec![targets[..index].to_vec(), targets[index..].to_vec()],
+    })
+}
+
+/// A tree predicting a numeric target attribute.
+#[derive(Debug, Default)]
+pub struct RegressionTree {
+    nodes: Vec<RegressionNode>,
+    config: TreeConfig,
+}
+
+impl RegressionTree {
+    pub fn new(config: TreeConfig) -> Self {
+        Self {
+            nodes: Vec::new(),
+            config,
+        }
+    }
+
+    /// Learns to predict attribute `target` of `data`, described by
+    /// `class_tags`, from the other attributes.
+    pub fn train(&mut self, data: &[Record], class_tags: &[Class], target: usize) {
+        self.nodes = Vec::new();
+        let config = self.config.clone();
+        let criterion = config.regression_criterion;
+        // Records without a target can't be learned from
+        let training = data
+            .iter()
+            .filter(|record| record.class_known())
+            .map(|record| (record, record.weight))
+            .collect();
+        let mut frontier = Frontier::new(training);
+        let mut leaf_count = 0;
+        while let Some((mut used_features, sub_set, parent_info, depth)) = frontier.pop() {
+            let targets: Vec<Weighted> = sub_set.iter().map(|(record, weight)| (record.target(), *weight)).collect();
+            let size = total_weight(&targets);
+            let pure = targets.iter().all(|(target, _)| *target == targets[0].0);
+            let can_grow = size >= config.min_samples_split as f64
+                && config.max_depth.is_none_or(|max_depth| depth < max_depth);
+            let best_split = if pure || !can_grow {
+                None
+            } else {
+                let candidates: Vec<Split> = candidate_features(class_tags, target, &used_features)
+                    .filter_map(|(feature, class_tag)| match class_tag {
+                        Class::Nominal(_) => {
+                            let children: Vec<Vec<Weighted>> = sub_set
+                                .iter()
+                                .filter_map(|(record, weight)| {
+                                    path_key(None, record.features[feature])
+                                        .map(|path| (path, (record.target(), *weight)))
+                                })
+                                .into_group_map()
+                                .into_values()
+                                .collect();
+                            let big_enough = children
+                                .iter()
+                                .all(|child| total_weight(child) >= config.min_samples_leaf as f64);
+                            if big_enough && !children.is_empty() {
+                                Some(Split {
+                                    feature,
+                                    threshold: None,
+                                    children,
+                                })
+                            } else {
+                                None
+                            }
+                        }
+                        Class::String(_) => None,
+                        Class::Continuous | Class::Date(_) => {
+                            best_threshold(criterion, config.min_samples_leaf, &sub_set, feature)
+                        }
+                    })
+                    .collect();
+                // Gain among the records with a known value, scaled by their
+                // share of the node. First best wins on ties.
+                candidates
+                    .into_iter()
+                    .map(|split| {
+                        let known = split.children.concat();
+                        let known_size = total_weight(&known);
+                        let gain = criterion.impurity(&known) - criterion.remaining(&split.children, known_size);
+                        (gain * known_size / size, split)
+                    })
+                    .fold(None, |best: Option<(f64, Split)>, (gain, split)| match best {
+                        Some((best_gain, _)) if best_gain >= gain => best,
+                        _ => Some((gain, split)),
+                    })
+                    .filter(|(gain, _)| *gain >= config.min_information_gain)
+                    .map(|(_, split)| split)
+            };
+            let best_split =
+                best_split.filter(|split| config.leaf_budget_allows(leaf_count, frontier.pending(), split.children.len()));
+            attach(&mut self.nodes, parent_info);
+            let value = config.leaf_prediction.predict(&targets);
+            if let Some(Split { feature, threshold, .. }) = best_split {
+                if threshold.is_none() {
+                    used_features.push(feature);
+                }
+                let mut child_sub_sets: HashMap<usize, Vec<(&Record, f64)>> = HashMap::new();
+                let mut missing = Vec::new();
+                for (record, weight) in sub_set {
+                    match path_key(threshold, record.features[feature]) {
+                        Some(path) => child_sub_sets.entry(path).or_default().push((record, weight)),
+                        None => missing.push((record, weight)),
+                    }
+                }
+                distribute_missing(&mut child_sub_sets, missing);
+                self.nodes.push(RegressionNode::Branch(RegressionBranch {
+                    paths: HashMap::new(),
+                    feature,
+                    threshold,
+                    value,
+                }));
+                frontier.push_children(&used_features, child_sub_sets, self.nodes.len() - 1, depth);
+            } else {
+                leaf_count += 1;
+                self.nodes.push(RegressionNode::Leaf(RegressionLeaf { value }));
+            }
+        }
+    }
+
+    pub fn predict(&self, record: &Record) -> f64 {
+        let mut node_index = 0;
+        loop {
+            match &self.nodes[node_index] {
+                RegressionNode::Branch(branch) => {
+                    let path = path_key(branch.threshold, record.features[branch.feature]);
+                    match path.and_then(|path| branch.paths.get(&path)) {
+                        Some(index) => node_index = *index,
+                        None => return branch.value,
+                    }
+                }
+                RegressionNode::Leaf(leaf) => return leaf.value,
+            }
+        }
+    }
+
+    pub fn print_tree(&self, depth: usize, class_tags: &[Class], labels: &[String]) {
+        print_levels(depth, class_tags, labels, |node_index| match &self.nodes[node_index] {
+            RegressionNode::Branch(branch) => PrintedNode::Branch {
+                feature: branch.feature,
+                threshold: branch.threshold,
+                paths: &branch.paths,
+            },
+            RegressionNode::Leaf(leaf) => PrintedNode::Leaf(leaf.value.to_string()),
+        });
+    }
+}
@@ -0,0 +1,92 @@
+use crate::arff::Class;
+use crate::record::{Record, Value};
+use std::str::FromStr;
+
+/// How `Scaler::fit` rescales numeric attributes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scaling {
+    /// Min-max normalization to [0, 1]
+    MinMax,
+    /// Z-score standardization to zero mean and unit standard deviation
+    ZScore,
+}
+
+impl FromStr for Scaling {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "normalize" | "minmax" => Ok(Scaling::MinMax),
+            "standardize" | "zscore" => Ok(Scaling::ZScore),
+            _ => Err(format!("unknown scaling {}", name)),
+        }
+    }
+}
+
+/// Scaling fitted to a dataset, so data read later can be scaled the same
+/// way. Every numeric attribute gets an offset and a scale, and its values
+/// become `(value - offset) / scale`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scaler {
+    pub scaling: Scaling,
+    /// Offset and scale of every attribute, `None` for those left alone
+    pub parameters: Vec<Option<(f64, f64)>>,
+}
+
+impl Scaler {
+    /// Fits `scaling` to the known values of every continuous attribute but
+    /// the `target`, weighting records by their weight. Dates, nominal and
+    /// string attributes are left alone, and so are attributes without
+    /// known values. Constant attributes only get the offset.
+    pub fn fit(records: &[Record], classes: &[Class], target: usize, scaling: Scaling) -> Self {
+        let parameters = classes
+            .iter()
+            .enumerate()
+            .map(|(index, class)| {
+                if index == target || !matches!(class, Class::Continuous) {
+                    return None;
+                }
+                let values: Vec<(f64, f64)> = records
+                    .iter()
+                    .filter_map(|record| match record.features[index] {
+                        Value::Continuous(value) => Some((value, record.weight)),
+                        _ => None,
+                    })
+                    .collect();
+                if values.is_empty() {
+                    return None;
+                }
+                let (offset, scale) = match scaling {
+                    Scaling::MinMax => {
+                        let min = values.iter().map(|(value, _)| *value).fold(f64::INFINITY, f64::min);
+                        let max = values.iter().map(|(value, _)| *value).fold(f64::NEG_INFINITY, f64::max);
+                        (min, max - min)
+                    }
+                    Scaling::ZScore => {
+                        let total: f64 = values.iter().map(|(_, weight)| weight).sum();
+                        if total <= 0. {
+                            return None;
+                        }
+                        let mean = values.iter().map(|(value, weight)| value * weight).sum::<f64>() / total;
+                        let variance = values
+                            .iter()
+                            .map(|(value, weight)| weight * (value - mean).powi(2))
+                            .sum::<f64>()
+                            / total;
+                        (mean, variance.sqrt())
+                    }
+                };
+                Some((offset, if scale > 0. { scale } else { 1. }))
+            })
+            .collect();
+        Self { scaling, parameters }
+    }
+
+    /// `value` of attribute `index` scaled.
+    pub fn apply(&self, index: usize, value: Value) -> Value {
+        match (value, self.parameters.get(index)) {
+            (Value::Continuous(value), Some(Some((offset, scale)))) => Value::Continuous((value - offset) / scale),
+            _ => value,
+        }
+    }
+}
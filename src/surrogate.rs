@@ -0,0 +1,156 @@
+use crate::arff::Class;
+use crate::decision::path_key;
+use crate::record::{Record, Value};
+use std::collections::HashMap;
+
+/// Another feature's split that sends training records down the same paths as
+/// a branch's primary split, used when the primary value is missing (CART).
+#[derive(Debug)]
+pub(crate) struct Surrogate {
+    pub feature: usize,
+    pub threshold: Option<f64>,
+    /// Primary path taken by each of the surrogate's own paths
+    pub paths: HashMap<usize, usize>,
+    /// Fraction of the training weight it routes like the primary split
+    pub agreement: f64,
+}
+
+impl Surrogate {
+    /// Primary path for `record`, if its surrogate value is known and was seen
+    /// in training.
+    pub fn primary_path(&self, record: &Record) -> Option<usize> {
+        path_key(self.threshold, record.features[self.feature])
+            .and_then(|path| self.paths.get(&path).cloned())
+    }
+}
+
+fn add_weight(counts: &mut Vec<f64>, path: usize, weight: f64) {
+    if counts.len() <= path {
+        counts.resize(path + 1, 0.);
+    }
+    counts[path] += weight;
+}
+
+/// Most common primary path among `counts`, with its weight.
+fn majority_path(counts: &[f64]) -> (usize, f64) {
+    counts
+        .iter()
+        .cloned()
+        .enumerate()
+        .fold((0, 0.), |best, (path, weight)| if weight > best.1 { (path, weight) } else { best })
+}
+
+/// Up to `count` surrogates for the split of `sub_set` on `feature`, best
+/// first. Only splits that agree more often than sending every record down
+/// the primary split's most common path are kept.
+pub(crate) fn find_surrogates(
+    sub_set: &[(&Record, f64)],
+    feature: usize,
+    threshold: Option<f64>,
+    class_tags: &[Class],
+    target: usize,
+    count: usize,
+) -> Vec<Surrogate> {
+    if count == 0 {
+        return Vec::new();
+    }
+    let primary: Vec<(&Record, usize, f64)> = sub_set
+        .iter()
+        .filter_map(|(record, weight)| {
+            path_key(threshold, record.features[feature]).map(|path| (*record, path, *weight))
+        })
+        .collect();
+    let mut surrogates: Vec<Surrogate> = class_tags
+        .iter()
+        .enumerate()
+        .filter(|(candidate, _)| *candidate != feature && *candidate != target)
+        .filter_map(|(candidate, class_tag)| {
+            let known: Vec<(Value, usize, f64)> = primary
+                .iter()
+                .map(|(record, path, weight)| (record.features[candidate], *path, *weight))
+                .filter(|(value, _, _)| *value != Value::Missing)
+                .collect();
+            let total: f64 = known.iter().map(|(_, _, weight)| weight).sum();
+            if total == 0. {
+                return None;
+            }
+            let mut primary_counts = Vec::new();
+            for (_, path, weight) in known.iter() {
+                add_weight(&mut primary_counts, *path, *weight);
+            }
+            let baseline = majority_path(&primary_counts).1 / total;
+            let surrogate = match class_tag {
+                Class::Nominal(_) => nominal_surrogate(candidate, &known, total),
+                Class::Continuous | Class::Date(_) => continuous_surrogate(candidate, known, total),
+                Class::String(_) => None,
+            };
+            surrogate.filter(|surrogate| surrogate.agreement > baseline)
+        })
+        .collect();
+    surrogates.sort_by(|x, y| y.agreement.total_cmp(&x.agreement));
+    surrogates.truncate(count);
+    surrogates
+}
+
+fn nominal_surrogate(feature: usize, known: &[(Value, usize, f64)], total: f64) -> Option<Surrogate> {
+    let mut groups: HashMap<usize, Vec<f64>> = HashMap::new();
+    for (value, path, weight) in known.iter() {
+        if let Some(own_path) = path_key(None, *value) {
+            add_weight(groups.entry(own_path).or_default(), *path, *weight);
+        }
+    }
+    let mut agreeing = 0.;
+    let paths = groups
+        .into_iter()
+        .map(|(own_path, counts)| {
+            let (path, weight) = majority_path(&counts);
+            agreeing += weight;
+            (own_path, path)
+        })
+        .collect();
+    Some(Surrogate {
+        feature,
+        threshold: None,
+        paths,
+        agreement: agreeing / total,
+    })
+}
+
+/// Best `value <= threshold` surrogate, trying the midpoint between every pair
+/// of adjacent distinct values.
+fn continuous_surrogate(feature: usize, known: Vec<(Value, usize, f64)>, total: f64) -> Option<Surrogate> {
+    let mut values: Vec<(f64, usize, f64)> = known
+        .into_iter()
+        .filter_map(|(value, path, weight)| match value {
+            Value::Continuous(value) => Some((value, path, weight)),
+            _ => None,
+        })
+        .collect();
+    values.sort_by(|x, y| x.0.total_cmp(&y.0));
+    let mut below = Vec::new();
+    let mut above = Vec::new();
+    for (_, path, weight) in values.iter() {
+        add_weight(&mut above, *path, *weight);
+    }
+    let mut best: Option<(f64, f64, usize, usize)> = None;
+    for pair in values.windows(2) {
+        let (value, path, weight) = pair[0];
+        add_weight(&mut below, path, weight);
+        add_weight(&mut above, path, -weight);
+        if value == pair[1].0 {
+            continue;
+        }
+        let (below_path, below_weight) = majority_path(&below);
+        let (above_path, above_weight) = majority_path(&above);
+        let agreement = (below_weight + above_weight) / total;
+        if best.is_none_or(|(best_agreement, _, _, _)| agreement > best_agreement) {
+            best = Some((agreement, (value + pair[1].0) / 2., below_path, above_path));
+        }
+    }
+    best.map(|(agreement, threshold, below_path, above_path)| Surrogate {
+        feature,
+        threshold: Some(threshold),
+        paths: vec![(0, below_path), (1, above_path)].into_iter().collect(),
+        agreement,
+    })
+}